@@ -0,0 +1,64 @@
+// Compares the NodeRef-based DirectedGraph with the index-based ArenaGraph on a large
+// random DAG. Run with `cargo bench --bench arena [nodes]`.
+
+use rust_dag::arena::ArenaGraph;
+use rust_dag::DirectedGraph;
+use std::time::{Duration, Instant};
+
+const EDGES_PER_NODE: usize = 4;
+
+// Edges of a DAG on 0..n: every node gets a few edges to later nodes, picked with a
+// small xorshift generator so that runs are reproducible.
+fn random_edges(n: usize) -> Vec<(usize, usize)> {
+    let mut state = 0x2545_f491_4f6c_dd1du64;
+    let mut edges = Vec::with_capacity(n * EDGES_PER_NODE);
+    for from in 0..n.saturating_sub(1) {
+        for _ in 0..EDGES_PER_NODE {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            let to = from + 1 + (state % (n - from - 1) as u64) as usize;
+            edges.push((from, to));
+        }
+    }
+    edges
+}
+
+fn time<R>(f: impl FnOnce() -> R) -> (R, Duration) {
+    let start = Instant::now();
+    let result = f();
+    (result, start.elapsed())
+}
+
+fn main() {
+    let n = std::env::args()
+        .skip(1)
+        .find_map(|arg| arg.parse().ok())
+        .unwrap_or(1_000_000);
+    let edges = random_edges(n);
+    println!("{} nodes, {} edges", n, edges.len());
+
+    let (graph, build) = time(|| {
+        let mut graph = DirectedGraph::default();
+        let nodes: Vec<_> = (0..n).map(|i| graph.add_node(i)).collect();
+        for &(from, to) in &edges {
+            graph.add_edge(&nodes[from], &nodes[to]);
+        }
+        graph
+    });
+    let (order, sort) = time(|| graph.topological_order().ok().unwrap());
+    assert_eq!(order.len(), n);
+    println!("DirectedGraph: build {:?}, sort {:?}", build, sort);
+
+    let (arena, build) = time(|| {
+        let mut arena = ArenaGraph::with_capacity(n, edges.len());
+        let nodes: Vec<_> = (0..n).map(|i| arena.add_node(i)).collect();
+        for &(from, to) in &edges {
+            arena.add_edge(nodes[from], nodes[to]);
+        }
+        arena
+    });
+    let (order, sort) = time(|| arena.topological_order().unwrap());
+    assert_eq!(order.len(), n);
+    println!("ArenaGraph:    build {:?}, sort {:?}", build, sort);
+}
@@ -0,0 +1,283 @@
+// An index-based alternative to DirectedGraph.
+//
+// Nodes and edges live in two contiguous vectors and refer to each other by index
+// instead of through Rc<RefCell<..>>, so there are no reference cycles, the graph is
+// Send and Sync whenever T is, and large graphs are much friendlier to the cache.
+// The adjacency lists are threaded through the edge vector as singly linked lists,
+// so adding an edge never allocates per node.
+
+use crate::DirectedGraph;
+use std::collections::{HashMap, VecDeque};
+use std::error::Error;
+use std::fmt;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct NodeId(usize);
+impl NodeId {
+    pub fn index(self) -> usize {
+        self.0
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct EdgeId(usize);
+impl EdgeId {
+    pub fn index(self) -> usize {
+        self.0
+    }
+}
+
+struct ArenaNode<T> {
+    data: T,
+    first_outgoing: Option<EdgeId>,
+    first_incoming: Option<EdgeId>,
+}
+
+struct ArenaEdge {
+    from: NodeId,
+    to: NodeId,
+    next_outgoing: Option<EdgeId>,
+    next_incoming: Option<EdgeId>,
+}
+
+pub struct ArenaGraph<T> {
+    nodes: Vec<ArenaNode<T>>,
+    edges: Vec<ArenaEdge>,
+}
+impl<T> Default for ArenaGraph<T> {
+    fn default() -> Self {
+        ArenaGraph {
+            nodes: Vec::new(),
+            edges: Vec::new(),
+        }
+    }
+}
+impl<T> ArenaGraph<T> {
+    pub fn with_capacity(nodes: usize, edges: usize) -> Self {
+        ArenaGraph {
+            nodes: Vec::with_capacity(nodes),
+            edges: Vec::with_capacity(edges),
+        }
+    }
+
+    pub fn add_node(&mut self, data: T) -> NodeId {
+        self.nodes.push(ArenaNode {
+            data,
+            first_outgoing: None,
+            first_incoming: None,
+        });
+        NodeId(self.nodes.len() - 1)
+    }
+
+    pub fn add_edge(&mut self, from: NodeId, to: NodeId) -> EdgeId {
+        let id = EdgeId(self.edges.len());
+        self.edges.push(ArenaEdge {
+            from,
+            to,
+            next_outgoing: self.nodes[from.0].first_outgoing.replace(id),
+            next_incoming: self.nodes[to.0].first_incoming.replace(id),
+        });
+        id
+    }
+
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn edge_count(&self) -> usize {
+        self.edges.len()
+    }
+
+    pub fn node_ids(&self) -> impl Iterator<Item = NodeId> {
+        (0..self.nodes.len()).map(NodeId)
+    }
+
+    pub fn data(&self, node: NodeId) -> &T {
+        &self.nodes[node.0].data
+    }
+
+    pub fn data_mut(&mut self, node: NodeId) -> &mut T {
+        &mut self.nodes[node.0].data
+    }
+
+    // The (from, to) endpoints of an edge.
+    pub fn endpoints(&self, edge: EdgeId) -> (NodeId, NodeId) {
+        let e = &self.edges[edge.0];
+        (e.from, e.to)
+    }
+
+    // The targets of the edges leaving `node`, most recently added edge first.
+    pub fn outgoing(&self, node: NodeId) -> impl Iterator<Item = NodeId> + '_ {
+        let mut next = self.nodes[node.0].first_outgoing;
+        std::iter::from_fn(move || {
+            let e = &self.edges[next?.0];
+            next = e.next_outgoing;
+            Some(e.to)
+        })
+    }
+
+    // The sources of the edges entering `node`, most recently added edge first.
+    pub fn incoming(&self, node: NodeId) -> impl Iterator<Item = NodeId> + '_ {
+        let mut next = self.nodes[node.0].first_incoming;
+        std::iter::from_fn(move || {
+            let e = &self.edges[next?.0];
+            next = e.next_incoming;
+            Some(e.from)
+        })
+    }
+
+    // Compute a topological sort using Kahn's algorithm, without modifying the graph.
+    // The adjacency is first compressed into a single offsets/targets array pair so
+    // the main loop only touches contiguous memory.
+    pub fn topological_order(&self) -> Result<Vec<NodeId>, CycleError> {
+        let n = self.nodes.len();
+        let mut in_degree = vec![0usize; n];
+        let mut offsets = vec![0usize; n + 1];
+        for e in &self.edges {
+            in_degree[e.to.0] += 1;
+            offsets[e.from.0 + 1] += 1;
+        }
+        for i in 0..n {
+            offsets[i + 1] += offsets[i];
+        }
+        let mut fill = offsets.clone();
+        let mut targets = vec![0usize; self.edges.len()];
+        for e in &self.edges {
+            targets[fill[e.from.0]] = e.to.0;
+            fill[e.from.0] += 1;
+        }
+
+        let mut s: VecDeque<usize> = (0..n).filter(|&i| in_degree[i] == 0).collect();
+        let mut result = Vec::with_capacity(n);
+        while let Some(i) = s.pop_front() {
+            result.push(NodeId(i));
+            for &j in &targets[offsets[i]..offsets[i + 1]] {
+                in_degree[j] -= 1;
+                if in_degree[j] == 0 {
+                    s.push_back(j);
+                }
+            }
+        }
+        if result.len() == n {
+            Ok(result)
+        } else {
+            Err(self.cycle_error(&in_degree))
+        }
+    }
+
+    // Every node left with a positive in-degree has a predecessor in the same situation,
+    // so walking backwards along those predecessors finds a cycle.
+    fn cycle_error(&self, in_degree: &[usize]) -> CycleError {
+        let unordered: Vec<_> = self.node_ids().filter(|n| in_degree[n.0] > 0).collect();
+        let mut path = Vec::new();
+        let mut position = HashMap::new();
+        let mut n = unordered[0];
+        while !position.contains_key(&n) {
+            position.insert(n, path.len());
+            path.push(n);
+            n = self.incoming(n).find(|m| in_degree[m.0] > 0).unwrap();
+        }
+        let mut cycle = path.split_off(position[&n]);
+        cycle.reverse();
+        cycle.rotate_right(1);
+        CycleError { cycle, unordered }
+    }
+}
+
+// Copy a DirectedGraph into an arena. Nodes keep their insertion order, so the i-th
+// node of the graph becomes NodeId(i). Arena edges carry no data, so edge data is
+// dropped.
+impl<T, E> From<&DirectedGraph<T, E>> for ArenaGraph<T>
+where
+    T: Clone,
+{
+    fn from(graph: &DirectedGraph<T, E>) -> Self {
+        let indices = graph.node_indices();
+        let mut result = ArenaGraph::with_capacity(graph.nodes.len(), 0);
+        for node in &graph.nodes {
+            result.add_node(node.ptr.borrow().data.clone());
+        }
+        for (i, node) in graph.nodes.iter().enumerate() {
+            for m in &node.ptr.borrow().outgoing {
+                result.add_edge(NodeId(i), NodeId(indices[m]));
+            }
+        }
+        result
+    }
+}
+
+// The arena counterpart of crate::CycleError, identifying nodes by NodeId.
+#[derive(Debug)]
+pub struct CycleError {
+    // The nodes of one concrete cycle, in edge order. The closing edge goes from the
+    // last node back to the first one.
+    pub cycle: Vec<NodeId>,
+    // All nodes that could not be ordered, in insertion order.
+    pub unordered: Vec<NodeId>,
+}
+
+impl fmt::Display for CycleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "cycle detected: ")?;
+        for (i, node) in self.cycle.iter().chain(self.cycle.first()).enumerate() {
+            if i > 0 {
+                write!(f, " -> ")?;
+            }
+            write!(f, "{}", node.0)?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for CycleError {}
+
+#[cfg(test)]
+mod tests {
+    use crate::arena::*;
+
+    #[test]
+    fn test_topological_order() {
+        // The diamond from the DirectedGraph test.
+        let mut graph = ArenaGraph::default();
+        let a = graph.add_node('A');
+        let b = graph.add_node('B');
+        let c = graph.add_node('C');
+        let d = graph.add_node('D');
+        graph.add_edge(a, b);
+        graph.add_edge(a, c);
+        graph.add_edge(b, d);
+        graph.add_edge(c, d);
+        assert_eq!(graph.outgoing(a).collect::<Vec<_>>(), vec![c, b]);
+        assert_eq!(graph.incoming(d).collect::<Vec<_>>(), vec![c, b]);
+
+        let order: String = graph
+            .topological_order()
+            .unwrap()
+            .into_iter()
+            .map(|n| *graph.data(n))
+            .collect();
+        assert_eq!(order, "ABCD");
+
+        graph.add_edge(d, a);
+        let err = graph.topological_order().unwrap_err();
+        assert_eq!(err.unordered, vec![a, b, c, d]);
+        assert_eq!(err.to_string(), "cycle detected: 0 -> 2 -> 3 -> 0");
+    }
+
+    #[test]
+    fn test_from_directed_graph() {
+        let mut graph = DirectedGraph::default();
+        let a = graph.add_node('A');
+        let b = graph.add_node('B');
+        graph.add_edge(&b, &a);
+        let arena = ArenaGraph::from(&graph);
+        assert_eq!(arena.node_count(), 2);
+        assert_eq!(arena.edge_count(), 1);
+        assert_eq!(arena.endpoints(EdgeId(0)), (NodeId(1), NodeId(0)));
+        assert_eq!(*arena.data(NodeId(1)), 'B');
+
+        // Arena graphs can be handed to other threads.
+        let handle = std::thread::spawn(move || arena.topological_order().unwrap());
+        assert_eq!(handle.join().unwrap(), vec![NodeId(1), NodeId(0)]);
+    }
+}
@@ -0,0 +1,229 @@
+// The async counterpart of Executor: run a future for every node of a DirectedGraph,
+// starting each node's future as soon as the futures of all of its predecessors have
+// resolved.
+//
+// Everything is driven from the single future returned by AsyncExecutor::run, which
+// polls the in-flight node futures itself, so no particular runtime is required. That
+// future borrows the graph and is therefore not Send; run it with a local executor
+// (e.g. a block_on or a LocalSet) or from within another task.
+
+use crate::executor::{adjacency, FailureMode, Outcomes, State, TaskOutcome};
+use crate::{CycleError, DirectedGraph};
+use std::future::{self, Future};
+use std::pin::Pin;
+use std::task::Poll;
+
+pub struct AsyncExecutor {
+    max_concurrency: usize,
+    failure_mode: FailureMode,
+}
+// By default, place no limit on the number of nodes in flight, and fail fast.
+impl Default for AsyncExecutor {
+    fn default() -> Self {
+        AsyncExecutor::new(usize::MAX, FailureMode::FailFast)
+    }
+}
+impl AsyncExecutor {
+    // Create an executor with at most `max_concurrency` node futures in flight at once
+    // (at least one).
+    pub fn new(max_concurrency: usize, failure_mode: FailureMode) -> Self {
+        AsyncExecutor {
+            max_concurrency: max_concurrency.max(1),
+            failure_mode,
+        }
+    }
+
+    // Call `f` on the data of every node of `graph` and await the resulting futures,
+    // never calling `f` on a node before the futures of all of its predecessors have
+    // succeeded. Downstream nodes of a failed node are skipped; in FailFast mode, the
+    // futures still in flight are dropped and reported as Cancelled. Returns an error
+    // without calling `f` if the graph has a cycle.
+    pub async fn run<T, L, R, E, F, Fut>(
+        &self,
+        graph: &DirectedGraph<T, L>,
+        f: F,
+    ) -> Result<Outcomes<T, R, E, L>, CycleError<T, L>>
+    where
+        F: Fn(&T) -> Fut,
+        Fut: Future<Output = Result<R, E>>,
+    {
+        graph.topological_order()?;
+        let (successors, pending) = adjacency(graph);
+        let mut state = State::new(pending);
+        let mut in_flight: Vec<(usize, Pin<Box<Fut>>)> = Vec::new();
+        future::poll_fn(|cx| loop {
+            while in_flight.len() < self.max_concurrency && !state.stopped {
+                match state.ready.pop_front() {
+                    Some(i) => {
+                        let started = f(&graph.nodes[i].ptr.borrow().data);
+                        in_flight.push((i, Box::pin(started)));
+                    }
+                    None => break,
+                }
+            }
+            let mut progress = false;
+            let mut k = 0;
+            while k < in_flight.len() {
+                match in_flight[k].1.as_mut().poll(cx) {
+                    Poll::Pending => k += 1,
+                    Poll::Ready(result) => {
+                        let (i, _) = in_flight.swap_remove(k);
+                        progress = true;
+                        match result {
+                            Ok(r) => {
+                                state.complete(i, TaskOutcome::Succeeded(r), &successors);
+                            }
+                            Err(e) => {
+                                state.complete(i, TaskOutcome::Failed(e), &successors);
+                                if self.failure_mode == FailureMode::FailFast {
+                                    state.stopped = true;
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+            if state.stopped {
+                // Dropping the futures still in flight cancels them.
+                for (i, _) in in_flight.drain(..) {
+                    state.outcomes[i] = Some(TaskOutcome::Cancelled);
+                }
+                return Poll::Ready(());
+            }
+            if in_flight.is_empty() && state.ready.is_empty() {
+                return Poll::Ready(());
+            }
+            // Completed nodes may have made others ready; start those before yielding.
+            if !progress {
+                return Poll::Pending;
+            }
+        })
+        .await;
+        Ok(state.into_outcomes(graph))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::async_executor::*;
+    use crate::NodeRef;
+    use std::cell::{Cell, RefCell};
+    use std::rc::Rc;
+    use std::sync::Arc;
+    use std::task::{Context, Wake, Waker};
+    use std::thread::{self, Thread};
+
+    // A minimal local executor, to show that no runtime is needed.
+    fn block_on<F: Future>(f: F) -> F::Output {
+        struct Unpark(Thread);
+        impl Wake for Unpark {
+            fn wake(self: Arc<Self>) {
+                self.0.unpark();
+            }
+        }
+        let waker = Waker::from(Arc::new(Unpark(thread::current())));
+        let mut cx = Context::from_waker(&waker);
+        let mut f = Box::pin(f);
+        loop {
+            if let Poll::Ready(result) = f.as_mut().poll(&mut cx) {
+                return result;
+            }
+            thread::park();
+        }
+    }
+
+    // A future that stays pending until `condition` holds.
+    async fn wait_until(condition: impl Fn() -> bool) {
+        future::poll_fn(|cx| {
+            if condition() {
+                Poll::Ready(())
+            } else {
+                cx.waker().wake_by_ref();
+                Poll::Pending
+            }
+        })
+        .await
+    }
+
+    // 'a' -> 'b', 'a' -> 'c' -> 'd'
+    fn graph() -> (DirectedGraph<char>, Vec<NodeRef<char>>) {
+        let mut graph = DirectedGraph::default();
+        let nodes: Vec<_> = ('a'..='d').map(|c| graph.add_node(c)).collect();
+        graph.add_edge(&nodes[0], &nodes[1]);
+        graph.add_edge(&nodes[0], &nodes[2]);
+        graph.add_edge(&nodes[2], &nodes[3]);
+        (graph, nodes)
+    }
+
+    #[test]
+    fn test_runs_concurrently_in_dependency_order() {
+        let (graph, nodes) = graph();
+        let started = Rc::new(RefCell::new(String::new()));
+        let outcomes = block_on(
+            AsyncExecutor::new(2, FailureMode::FailFast).run(&graph, |&c| {
+                started.borrow_mut().push(c);
+                let started = started.clone();
+                async move {
+                    // 'b' and 'c' only finish once both have started, so this would hang
+                    // if they were not in flight at the same time.
+                    if c == 'b' || c == 'c' {
+                        wait_until(|| {
+                            started.borrow().contains('b') && started.borrow().contains('c')
+                        })
+                        .await;
+                    }
+                    Ok::<_, ()>(c.to_ascii_uppercase())
+                }
+            }),
+        )
+        .ok()
+        .unwrap();
+        assert_eq!(started.borrow().as_str(), "abcd");
+        assert_eq!(outcomes[&nodes[3]], TaskOutcome::Succeeded('D'));
+    }
+
+    #[test]
+    fn test_failure_cancels_downstream_nodes() {
+        let (graph, nodes) = graph();
+        let run = |mode| {
+            // 'b' waits until it is polled for the third time, 'c' fails right away.
+            let polls = Rc::new(Cell::new(0));
+            let outcomes = block_on(AsyncExecutor::new(2, mode).run(&graph, |&c| {
+                let polls = polls.clone();
+                async move {
+                    match c {
+                        'b' => {
+                            wait_until(|| {
+                                polls.set(polls.get() + 1);
+                                polls.get() >= 3
+                            })
+                            .await
+                        }
+                        'c' => return Err("boom"),
+                        _ => {}
+                    }
+                    Ok(c)
+                }
+            }));
+            outcomes.ok().unwrap()
+        };
+
+        let outcomes = run(FailureMode::FailFast);
+        assert_eq!(outcomes[&nodes[0]], TaskOutcome::Succeeded('a'));
+        assert_eq!(outcomes[&nodes[1]], TaskOutcome::Cancelled);
+        assert_eq!(outcomes[&nodes[2]], TaskOutcome::Failed("boom"));
+        assert_eq!(outcomes[&nodes[3]], TaskOutcome::Skipped);
+
+        let outcomes = run(FailureMode::KeepGoing);
+        assert_eq!(outcomes[&nodes[1]], TaskOutcome::Succeeded('b'));
+        assert_eq!(outcomes[&nodes[3]], TaskOutcome::Skipped);
+    }
+
+    #[test]
+    fn test_cycle_is_rejected() {
+        let (mut graph, nodes) = graph();
+        graph.add_edge(&nodes[3], &nodes[0]);
+        let result = block_on(AsyncExecutor::default().run(&graph, |_| async { Ok::<_, ()>(()) }));
+        assert!(result.is_err());
+    }
+}
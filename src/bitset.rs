@@ -0,0 +1,38 @@
+// A fixed-capacity set of small integers, used for the reachability computations.
+#[derive(Clone)]
+pub(crate) struct BitSet {
+    words: Vec<u64>,
+}
+impl BitSet {
+    pub(crate) fn new(capacity: usize) -> Self {
+        BitSet {
+            words: vec![0; capacity.div_ceil(64)],
+        }
+    }
+
+    pub(crate) fn insert(&mut self, i: usize) {
+        self.words[i / 64] |= 1 << (i % 64);
+    }
+
+    pub(crate) fn remove(&mut self, i: usize) {
+        self.words[i / 64] &= !(1 << (i % 64));
+    }
+
+    pub(crate) fn contains(&self, i: usize) -> bool {
+        self.words[i / 64] & 1 << (i % 64) != 0
+    }
+
+    pub(crate) fn union_with(&mut self, other: &BitSet) {
+        for (word, other) in self.words.iter_mut().zip(&other.words) {
+            *word |= other;
+        }
+    }
+
+    pub(crate) fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        self.words.iter().enumerate().flat_map(|(w, &word)| {
+            (0..64)
+                .filter(move |b| word & 1 << b != 0)
+                .map(move |b| w * 64 + b)
+        })
+    }
+}
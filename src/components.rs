@@ -0,0 +1,200 @@
+use crate::{DirectedGraph, NodeRef};
+use std::collections::HashSet;
+
+impl<T, E> DirectedGraph<T, E> {
+    // Split the graph into strongly connected components: maximal sets of nodes that
+    // can all reach each other. A node that is not on any cycle is a component of its
+    // own. Uses Tarjan's algorithm, without recursion so that long paths cannot overflow
+    // the stack.
+    //
+    // The components are returned in topological order: if there is an edge from one
+    // component to another, the first one comes first. The nodes of each component are
+    // in insertion order.
+    pub fn strongly_connected_components(&self) -> Vec<Vec<NodeRef<T, E>>> {
+        let indices = self.node_indices();
+        let successors: Vec<Vec<usize>> = self
+            .nodes
+            .iter()
+            .map(|node| {
+                node.ptr
+                    .borrow()
+                    .outgoing
+                    .iter()
+                    .map(|m| indices[m])
+                    .collect()
+            })
+            .collect();
+        let n = self.nodes.len();
+        // visit_index[i] is the order in which node i was first visited
+        let mut visit_index: Vec<Option<usize>> = vec![None; n];
+        // low_link[i] is the smallest visit index reachable from node i's subtree through
+        // nodes still on the stack
+        let mut low_link = vec![0; n];
+        let mut on_stack = vec![false; n];
+        let mut stack = Vec::new();
+        let mut visited = 0;
+        let mut components = Vec::new();
+        for root in 0..n {
+            if visit_index[root].is_some() {
+                continue;
+            }
+            // The simulated recursion: a node and how many of its successors are done.
+            let mut call_stack = vec![(root, 0)];
+            visit_index[root] = Some(visited);
+            low_link[root] = visited;
+            visited += 1;
+            stack.push(root);
+            on_stack[root] = true;
+            while let Some(&mut (v, ref mut next)) = call_stack.last_mut() {
+                if let Some(&w) = successors[v].get(*next) {
+                    *next += 1;
+                    match visit_index[w] {
+                        None => {
+                            visit_index[w] = Some(visited);
+                            low_link[w] = visited;
+                            visited += 1;
+                            stack.push(w);
+                            on_stack[w] = true;
+                            call_stack.push((w, 0));
+                        }
+                        Some(index) if on_stack[w] => low_link[v] = low_link[v].min(index),
+                        Some(_) => {}
+                    }
+                    continue;
+                }
+                // All successors of v are done.
+                call_stack.pop();
+                if let Some(&(parent, _)) = call_stack.last() {
+                    low_link[parent] = low_link[parent].min(low_link[v]);
+                }
+                if Some(low_link[v]) == visit_index[v] {
+                    // v is the root of a component: everything above it on the stack.
+                    let mut component = Vec::new();
+                    loop {
+                        let w = stack.pop().unwrap();
+                        on_stack[w] = false;
+                        component.push(w);
+                        if w == v {
+                            break;
+                        }
+                    }
+                    component.sort_unstable();
+                    components.push(component);
+                }
+            }
+        }
+        // Tarjan's algorithm finds the components in reverse topological order.
+        components
+            .into_iter()
+            .rev()
+            .map(|component| {
+                component
+                    .into_iter()
+                    .map(|i| self.nodes[i].clone())
+                    .collect()
+            })
+            .collect()
+    }
+
+    // Collapse every strongly connected component into a single node, whose data is the
+    // component's nodes. The result is a DAG, with an edge from one component to another
+    // if there is an edge between their nodes in this graph (parallel edges are merged,
+    // and edges within a component dropped). Its nodes are added in topological order,
+    // as returned by strongly_connected_components.
+    pub fn condensation(&self) -> DirectedGraph<Vec<NodeRef<T, E>>> {
+        let components = self.strongly_connected_components();
+        let mut component_of = self.node_indices();
+        for (c, component) in components.iter().enumerate() {
+            for node in component {
+                component_of.insert(node.clone(), c);
+            }
+        }
+        let mut result = DirectedGraph::default();
+        let condensed: Vec<_> = components
+            .iter()
+            .map(|component| result.add_node(component.clone()))
+            .collect();
+        let mut edges = HashSet::new();
+        for (c, component) in components.iter().enumerate() {
+            for node in component {
+                for m in &node.ptr.borrow().outgoing {
+                    let d = component_of[m];
+                    if c != d && edges.insert((c, d)) {
+                        result.add_edge(&condensed[c], &condensed[d]);
+                    }
+                }
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    fn to_string(nodes: &[NodeRef<char>]) -> String {
+        nodes.iter().map(|node| node.ptr.borrow().data).collect()
+    }
+
+    // Two cycles A -> B -> C -> A and D -> E -> D, connected by C -> D and B -> E, a
+    // self loop on F, and G feeding into the first cycle.
+    fn graph() -> (DirectedGraph<char>, Vec<NodeRef<char>>) {
+        let mut graph = DirectedGraph::default();
+        let n: Vec<_> = ('A'..='G').map(|c| graph.add_node(c)).collect();
+        for &(from, to) in &[
+            (0, 1),
+            (1, 2),
+            (2, 0),
+            (3, 4),
+            (4, 3),
+            (2, 3),
+            (1, 4),
+            (5, 5),
+            (6, 0),
+        ] {
+            graph.add_edge(&n[from], &n[to]);
+        }
+        (graph, n)
+    }
+
+    #[test]
+    fn test_strongly_connected_components() {
+        let (graph, _) = graph();
+        let components: Vec<_> = graph
+            .strongly_connected_components()
+            .iter()
+            .map(|c| to_string(c))
+            .collect();
+        assert_eq!(components, vec!["G", "F", "ABC", "DE"]);
+
+        // A long chain does not overflow the stack.
+        let mut graph = DirectedGraph::default();
+        let nodes: Vec<_> = (0..100_000).map(|i| graph.add_node(i)).collect();
+        for pair in nodes.windows(2) {
+            graph.add_edge(&pair[0], &pair[1]);
+        }
+        assert_eq!(graph.strongly_connected_components().len(), 100_000);
+        graph.add_edge(&nodes[99_999], &nodes[0]);
+        assert_eq!(graph.strongly_connected_components().len(), 1);
+    }
+
+    #[test]
+    fn test_condensation() {
+        let (graph, _) = graph();
+        assert!(graph.topological_order().is_err());
+        let condensed = graph.condensation();
+        let order: Vec<_> = condensed
+            .topological_order()
+            .ok()
+            .unwrap()
+            .iter()
+            .map(|c| to_string(&c.ptr.borrow().data))
+            .collect();
+        assert_eq!(order, vec!["G", "F", "ABC", "DE"]);
+        let abc = &condensed.nodes()[2];
+        assert_eq!(abc.ptr.borrow().outgoing.len(), 1);
+        assert_eq!(abc.ptr.borrow().incoming.len(), 1);
+        assert!(condensed.nodes()[1].ptr.borrow().outgoing.is_empty());
+    }
+}
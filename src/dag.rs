@@ -0,0 +1,343 @@
+use crate::{CycleError, DirectedGraph, NodeRef};
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fmt;
+
+// A directed graph that is acyclic by construction: an edge that would close a cycle
+// is rejected when it is added, rather than discovered later when sorting.
+//
+// A topological order of the nodes is maintained incrementally using the dynamic
+// topological sort algorithm of Pearce and Kelly. Adding an edge that agrees with the
+// current order is O(1); otherwise only the nodes between the edge's endpoints in the
+// current order are visited and reordered.
+pub struct Dag<T, E = ()> {
+    graph: DirectedGraph<T, E>,
+    // ord[n] is the position of n in the maintained topological order. Positions are
+    // unique but not necessarily contiguous.
+    ord: HashMap<NodeRef<T, E>, usize>,
+    next_ord: usize,
+}
+
+// The error returned when Dag::try_add_edge rejects an edge.
+pub enum AddEdgeError<T, E = ()> {
+    // The edge would close a cycle.
+    Cycle(CycleError<T, E>),
+    // The endpoint is not a node of the Dag: it was removed, or belongs to another graph.
+    NodeNotInGraph(NodeRef<T, E>),
+}
+
+impl<T, E> fmt::Debug for AddEdgeError<T, E>
+where
+    T: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AddEdgeError::Cycle(error) => f.debug_tuple("Cycle").field(error).finish(),
+            AddEdgeError::NodeNotInGraph(node) => {
+                f.debug_tuple("NodeNotInGraph").field(node).finish()
+            }
+        }
+    }
+}
+
+impl<T, E> fmt::Display for AddEdgeError<T, E>
+where
+    T: fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AddEdgeError::Cycle(error) => error.fmt(f),
+            AddEdgeError::NodeNotInGraph(node) => {
+                write!(f, "node {} is not in the graph", node.ptr.borrow().data)
+            }
+        }
+    }
+}
+
+impl<T, E> Error for AddEdgeError<T, E> where T: fmt::Debug + fmt::Display {}
+
+// Like DirectedGraph, only implemented without edge data; use new() otherwise.
+impl<T> Default for Dag<T> {
+    fn default() -> Self {
+        Dag::new()
+    }
+}
+impl<T, E> Dag<T, E> {
+    pub fn new() -> Self {
+        Dag {
+            graph: DirectedGraph::new(),
+            ord: HashMap::new(),
+            next_ord: 0,
+        }
+    }
+
+    // Turn a graph into a Dag, failing if it contains a cycle.
+    pub fn from_graph(graph: DirectedGraph<T, E>) -> Result<Self, CycleError<T, E>> {
+        let ord: HashMap<_, _> = graph
+            .topological_order()?
+            .into_iter()
+            .enumerate()
+            .map(|(i, n)| (n, i))
+            .collect();
+        let next_ord = ord.len();
+        Ok(Dag {
+            graph,
+            ord,
+            next_ord,
+        })
+    }
+
+    pub fn graph(&self) -> &DirectedGraph<T, E> {
+        &self.graph
+    }
+
+    pub fn into_graph(self) -> DirectedGraph<T, E> {
+        self.graph
+    }
+
+    pub fn add_node(&mut self, data: T) -> NodeRef<T, E> {
+        // A new node has no edges, so it can go anywhere in the order; put it last.
+        let result = self.graph.add_node(data);
+        self.ord.insert(result.clone(), self.next_ord);
+        self.next_ord += 1;
+        result
+    }
+
+    // Add an edge from `from` to `to` with default data, unless it would close a cycle.
+    // In that case the graph is left unchanged and the error's cycle is the would-be
+    // cycle, starting with `from`, `to` and continuing along the existing path from `to`
+    // back to `from`. An endpoint that is not in the Dag is reported as NodeNotInGraph.
+    pub fn try_add_edge(
+        &mut self,
+        from: &NodeRef<T, E>,
+        to: &NodeRef<T, E>,
+    ) -> Result<(), AddEdgeError<T, E>>
+    where
+        E: Default,
+    {
+        self.try_add_edge_with(from, to, E::default())
+    }
+
+    // Like try_add_edge, with the given edge data.
+    pub fn try_add_edge_with(
+        &mut self,
+        from: &NodeRef<T, E>,
+        to: &NodeRef<T, E>,
+        data: E,
+    ) -> Result<(), AddEdgeError<T, E>> {
+        let position = |node: &NodeRef<T, E>| match self.ord.get(node) {
+            Some(&position) => Ok(position),
+            None => Err(AddEdgeError::NodeNotInGraph(node.clone())),
+        };
+        let upper = position(from)?;
+        let lower = position(to)?;
+        if lower <= upper {
+            // `to` is ordered before `from` (or is `from`). Find the nodes reachable from
+            // `to` that are ordered no later than `from`; if `from` is one of them the
+            // edge would close a cycle.
+            let forward = match self.forward_search(to, from, upper) {
+                Ok(forward) => forward,
+                Err(cycle) => {
+                    return Err(AddEdgeError::Cycle(CycleError {
+                        unordered: cycle.clone(),
+                        cycle,
+                    }))
+                }
+            };
+            let backward = self.backward_search(from, lower);
+            self.reorder(backward, forward);
+        }
+        self.graph.add_edge_with(from, to, data);
+        Ok(())
+    }
+
+    // Removing nodes or edges cannot create a cycle, and the maintained order stays valid.
+    pub fn remove_node(&mut self, node: &NodeRef<T, E>) -> bool {
+        self.ord.remove(node);
+        self.graph.remove_node(node)
+    }
+
+    pub fn remove_edge(&mut self, from: &NodeRef<T, E>, to: &NodeRef<T, E>) -> bool {
+        self.graph.remove_edge(from, to)
+    }
+
+    pub fn clear_edges_of(&mut self, node: &NodeRef<T, E>) {
+        self.graph.clear_edges_of(node)
+    }
+
+    // The nodes of the graph in the maintained topological order.
+    pub fn topological_order(&self) -> Vec<NodeRef<T, E>> {
+        let mut result: Vec<_> = self.graph.nodes.to_vec();
+        result.sort_by_key(|n| self.ord[n]);
+        result
+    }
+
+    // Depth-first search along outgoing edges from `start`, visiting only nodes ordered
+    // no later than `upper`. Returns the visited nodes, or the cycle through `target`
+    // if `target` is reached.
+    #[allow(clippy::type_complexity)]
+    fn forward_search(
+        &self,
+        start: &NodeRef<T, E>,
+        target: &NodeRef<T, E>,
+        upper: usize,
+    ) -> Result<Vec<NodeRef<T, E>>, Vec<NodeRef<T, E>>> {
+        let mut visited = vec![start.clone()];
+        let mut parent: HashMap<NodeRef<T, E>, NodeRef<T, E>> = HashMap::new();
+        let mut stack = vec![start.clone()];
+        while let Some(n) = stack.pop() {
+            if n == *target {
+                // Walk back along the search tree to build target -> start -> ... -> n.
+                let mut cycle = vec![n.clone()];
+                let mut m = n;
+                while m != *start {
+                    m = parent[&m].clone();
+                    cycle.push(m.clone());
+                }
+                cycle.push(target.clone());
+                cycle.reverse();
+                cycle.pop();
+                return Err(cycle);
+            }
+            for m in &n.ptr.borrow().outgoing {
+                if self.ord[m] <= upper && !parent.contains_key(m) && m != start {
+                    parent.insert(m.clone(), n.clone());
+                    visited.push(m.clone());
+                    stack.push(m.clone());
+                }
+            }
+        }
+        Ok(visited)
+    }
+
+    // Depth-first search along incoming edges from `start`, visiting only nodes ordered
+    // no earlier than `lower`.
+    fn backward_search(&self, start: &NodeRef<T, E>, lower: usize) -> Vec<NodeRef<T, E>> {
+        let mut visited: HashSet<_> = vec![start.clone()].into_iter().collect();
+        let mut stack = vec![start.clone()];
+        while let Some(n) = stack.pop() {
+            for m in &n.ptr.borrow().incoming {
+                if self.ord[m] >= lower && visited.insert(m.clone()) {
+                    stack.push(m.clone());
+                }
+            }
+        }
+        visited.into_iter().collect()
+    }
+
+    // Reassign the positions of the affected nodes so that everything that reaches the
+    // new edge's source comes before everything reachable from its target, reusing the
+    // same pool of positions.
+    fn reorder(&mut self, mut backward: Vec<NodeRef<T, E>>, mut forward: Vec<NodeRef<T, E>>) {
+        backward.sort_by_key(|n| self.ord[n]);
+        forward.sort_by_key(|n| self.ord[n]);
+        let mut pool: Vec<usize> = backward
+            .iter()
+            .chain(forward.iter())
+            .map(|n| self.ord[n])
+            .collect();
+        pool.sort_unstable();
+        for (n, position) in backward.into_iter().chain(forward).zip(pool) {
+            self.ord.insert(n, position);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    #[test]
+    fn test_try_add_edge() {
+        let mut dag = Dag::default();
+        let a = dag.add_node('A');
+        let b = dag.add_node('B');
+        let c = dag.add_node('C');
+        let d = dag.add_node('D');
+        // These edges go against the insertion order, so they force reordering.
+        dag.try_add_edge(&d, &a).unwrap();
+        dag.try_add_edge(&c, &d).unwrap();
+        dag.try_add_edge(&b, &c).unwrap();
+        let order: String = dag
+            .topological_order()
+            .iter()
+            .map(|n| n.ptr.borrow().data)
+            .collect();
+        assert_eq!(order, "BCDA");
+
+        // A -> C would close the cycle A -> C -> D -> A.
+        let err = dag.try_add_edge(&a, &c).err().unwrap();
+        assert_eq!(err.to_string(), "cycle detected: A -> C -> D -> A");
+        match err {
+            AddEdgeError::Cycle(err) => {
+                assert_eq!(err.cycle, vec![a.clone(), c.clone(), d.clone()])
+            }
+            AddEdgeError::NodeNotInGraph(_) => panic!("expected a cycle"),
+        }
+        assert!(a.ptr.borrow().outgoing.is_empty());
+        assert!(dag.try_add_edge(&b, &b).is_err());
+        // Edges that agree with the order are fine.
+        dag.try_add_edge(&b, &a).unwrap();
+        assert!(dag.graph().topological_order().is_ok());
+
+        // Once D -> A is gone, A -> C is fine.
+        assert!(dag.remove_edge(&d, &a));
+        dag.try_add_edge(&a, &c).unwrap();
+        assert!(dag.remove_node(&d));
+        assert_eq!(
+            dag.topological_order(),
+            vec![b.clone(), a.clone(), c.clone()]
+        );
+
+        // Removed nodes and nodes of other graphs are rejected.
+        let err = dag.try_add_edge(&a, &d).err().unwrap();
+        assert_eq!(err.to_string(), "node D is not in the graph");
+        let other = Dag::default().add_node('X');
+        assert!(matches!(
+            dag.try_add_edge(&other, &b),
+            Err(AddEdgeError::NodeNotInGraph(node)) if node == other
+        ));
+        assert_eq!(a.ptr.borrow().outgoing, vec![c]);
+    }
+
+    #[test]
+    fn test_order_is_maintained() {
+        // Insert the edges of a DAG on 0..n (i -> j for i > j whenever j divides i)
+        // in an order that keeps invalidating the current topological order.
+        let n = 60;
+        let mut dag = Dag::default();
+        let nodes: Vec<_> = (0..n).map(|i| dag.add_node(i)).collect();
+        for j in 1..n {
+            for i in (j + 1..n).filter(|i| i % j == 0) {
+                dag.try_add_edge(&nodes[i], &nodes[j]).unwrap();
+            }
+        }
+        let position: HashMap<_, _> = dag
+            .topological_order()
+            .into_iter()
+            .enumerate()
+            .map(|(p, n)| (n.ptr.borrow().data, p))
+            .collect();
+        for j in 1..n {
+            for i in (j + 1..n).filter(|i| i % j == 0) {
+                assert!(position[&i] < position[&j]);
+                assert!(dag.try_add_edge(&nodes[j], &nodes[i]).is_err());
+            }
+        }
+    }
+
+    #[test]
+    fn test_from_graph() {
+        let mut graph = DirectedGraph::default();
+        let a = graph.add_node('A');
+        let b = graph.add_node('B');
+        graph.add_edge(&a, &b);
+        let mut dag = Dag::from_graph(graph).ok().unwrap();
+        assert!(dag.try_add_edge(&b, &a).is_err());
+
+        let mut graph = DirectedGraph::default();
+        let a = graph.add_node('A');
+        graph.add_edge(&a, &a);
+        assert!(Dag::from_graph(graph).is_err());
+    }
+}
@@ -0,0 +1,464 @@
+// Mermaid flowchart and PlantUML output for DirectedGraph, for diagrams in docs and
+// PR descriptions.
+//
+// Both formats show node labels and refer to nodes by ID, so every node gets an ID
+// derived from its label: ASCII letters and digits are kept and everything else
+// becomes '_', with a prefix where the result would be empty, start with a digit or be
+// a keyword, and a numeric suffix where it would repeat an earlier ID.
+
+use crate::{DirectedGraph, NodeRef};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+type GroupKey<'a, T> = Box<dyn Fn(&T) -> Option<String> + 'a>;
+type EdgeLabel<'a, T, E> = Box<dyn Fn(&T, &E, &T) -> Option<String> + 'a>;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Mermaid,
+    PlantUml,
+}
+
+// A Mermaid or PlantUML rendering of a graph, created by DirectedGraph::to_mermaid or
+// DirectedGraph::to_plantuml. Like Dot, it implements Display.
+pub struct Diagram<'a, T, E = ()> {
+    graph: &'a DirectedGraph<T, E>,
+    format: Format,
+    node_label: Box<dyn Fn(&T) -> String + 'a>,
+    group_key: Option<GroupKey<'a, T>>,
+    edge_label: Option<EdgeLabel<'a, T, E>>,
+    path: Vec<NodeRef<T, E>>,
+}
+
+impl<T, E> DirectedGraph<T, E> {
+    // Render the graph as a Mermaid flowchart, labelling every node with `node_label`.
+    pub fn to_mermaid<'a, F>(&'a self, node_label: F) -> Diagram<'a, T, E>
+    where
+        F: Fn(&T) -> String + 'a,
+    {
+        Diagram::new(self, Format::Mermaid, Box::new(node_label))
+    }
+
+    // Render the graph as a PlantUML diagram, labelling every node with `node_label`.
+    pub fn to_plantuml<'a, F>(&'a self, node_label: F) -> Diagram<'a, T, E>
+    where
+        F: Fn(&T) -> String + 'a,
+    {
+        Diagram::new(self, Format::PlantUml, Box::new(node_label))
+    }
+}
+
+impl<'a, T, E> Diagram<'a, T, E> {
+    fn new(
+        graph: &'a DirectedGraph<T, E>,
+        format: Format,
+        node_label: Box<dyn Fn(&T) -> String + 'a>,
+    ) -> Self {
+        Diagram {
+            graph,
+            format,
+            node_label,
+            group_key: None,
+            edge_label: None,
+            path: Vec::new(),
+        }
+    }
+
+    // Draw the nodes for which `key` returns the same group inside one box (a Mermaid
+    // subgraph or a PlantUML package) labelled with the group. Nodes without a group
+    // are drawn outside of all boxes.
+    pub fn group_by<F>(mut self, key: F) -> Self
+    where
+        F: Fn(&T) -> Option<String> + 'a,
+    {
+        self.group_key = Some(Box::new(key));
+        self
+    }
+
+    // Label the edges for which `f` returns a label, given the data of the source, the
+    // edge itself and the target.
+    pub fn edge_label<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&T, &E, &T) -> Option<String> + 'a,
+    {
+        self.edge_label = Some(Box::new(f));
+        self
+    }
+
+    // Draw the nodes of `path` and an edge between every two consecutive ones in red
+    // and bold, e.g. to show a critical path or a cycle from a CycleError.
+    pub fn highlight_path(mut self, path: &[NodeRef<T, E>]) -> Self {
+        self.path = path.to_vec();
+        self
+    }
+
+    fn layout(&self) -> Layout {
+        let indices = self.graph.node_indices();
+        let nodes = &self.graph.nodes;
+        let mut used = HashSet::new();
+        let labels: Vec<String> = nodes
+            .iter()
+            .map(|node| (self.node_label)(&node.ptr.borrow().data))
+            .collect();
+        let ids = labels
+            .iter()
+            .map(|label| unique_id(label, &mut used))
+            .collect();
+
+        let mut groups: Vec<Group> = Vec::new();
+        let mut ungrouped = Vec::new();
+        for (i, node) in nodes.iter().enumerate() {
+            let key = match &self.group_key {
+                Some(group_key) => group_key(&node.ptr.borrow().data),
+                None => None,
+            };
+            match key {
+                None => ungrouped.push(i),
+                Some(key) => match groups.iter_mut().find(|group| group.label == key) {
+                    Some(group) => group.nodes.push(i),
+                    None => groups.push(Group {
+                        id: unique_id(&key, &mut used),
+                        label: key,
+                        nodes: vec![i],
+                    }),
+                },
+            }
+        }
+
+        let mut highlighted_nodes = vec![false; nodes.len()];
+        // Like Dot::highlight, nodes that are not in the graph are ignored, along with
+        // the steps of the path to and from them.
+        let path: Vec<Option<usize>> = self
+            .path
+            .iter()
+            .map(|node| indices.get(node).copied())
+            .collect();
+        for &i in path.iter().flatten() {
+            highlighted_nodes[i] = true;
+        }
+        // The number of steps of the path along each pair of nodes still to be assigned
+        // an edge.
+        let mut steps: HashMap<(usize, usize), usize> = HashMap::new();
+        for pair in path.windows(2) {
+            if let [Some(i), Some(j)] = *pair {
+                *steps.entry((i, j)).or_insert(0) += 1;
+            }
+        }
+        let mut edges = Vec::new();
+        for (i, node) in nodes.iter().enumerate() {
+            let node = node.ptr.borrow();
+            for (m, e) in node.outgoing_edges() {
+                let j = indices[m];
+                let label = match &self.edge_label {
+                    Some(edge_label) => edge_label(&node.data, e, &m.ptr.borrow().data),
+                    None => None,
+                };
+                let highlighted = match steps.get_mut(&(i, j)) {
+                    Some(count) if *count > 0 => {
+                        *count -= 1;
+                        true
+                    }
+                    _ => false,
+                };
+                edges.push(Edge {
+                    from: i,
+                    to: j,
+                    label,
+                    highlighted,
+                });
+            }
+        }
+        Layout {
+            ids,
+            labels,
+            groups,
+            ungrouped,
+            highlighted_nodes,
+            edges,
+        }
+    }
+}
+
+struct Group {
+    id: String,
+    label: String,
+    nodes: Vec<usize>,
+}
+
+struct Edge {
+    from: usize,
+    to: usize,
+    label: Option<String>,
+    highlighted: bool,
+}
+
+// What is drawn, with nodes by index into DirectedGraph::nodes.
+struct Layout {
+    ids: Vec<String>,
+    labels: Vec<String>,
+    groups: Vec<Group>,
+    ungrouped: Vec<usize>,
+    highlighted_nodes: Vec<bool>,
+    edges: Vec<Edge>,
+}
+
+const HIGHLIGHT_STYLE: &str = "stroke:#d00,stroke-width:3px";
+
+impl<T, E> fmt::Display for Diagram<'_, T, E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let layout = self.layout();
+        match self.format {
+            Format::Mermaid => write_mermaid(f, &layout),
+            Format::PlantUml => write_plantuml(f, &layout),
+        }
+    }
+}
+
+fn write_mermaid(f: &mut fmt::Formatter, layout: &Layout) -> fmt::Result {
+    let node = |f: &mut fmt::Formatter, i: usize, indent: &str| {
+        let label = escape_mermaid(&layout.labels[i]);
+        writeln!(f, "{}{}[\"{}\"]", indent, layout.ids[i], label)
+    };
+    writeln!(f, "flowchart TD")?;
+    for group in &layout.groups {
+        let label = escape_mermaid(&group.label);
+        writeln!(f, "    subgraph {}[\"{}\"]", group.id, label)?;
+        for &i in &group.nodes {
+            node(f, i, "        ")?;
+        }
+        writeln!(f, "    end")?;
+    }
+    for &i in &layout.ungrouped {
+        node(f, i, "    ")?;
+    }
+    for edge in &layout.edges {
+        let (from, to) = (&layout.ids[edge.from], &layout.ids[edge.to]);
+        match &edge.label {
+            Some(label) => writeln!(f, "    {} -->|\"{}\"| {}", from, escape_mermaid(label), to)?,
+            None => writeln!(f, "    {} --> {}", from, to)?,
+        }
+    }
+    let nodes: Vec<&str> = (0..layout.ids.len())
+        .filter(|&i| layout.highlighted_nodes[i])
+        .map(|i| layout.ids[i].as_str())
+        .collect();
+    if !nodes.is_empty() {
+        writeln!(f, "    classDef highlighted {}", HIGHLIGHT_STYLE)?;
+        writeln!(f, "    class {} highlighted", nodes.join(","))?;
+    }
+    // Mermaid styles edges by their position in the diagram.
+    let edges: Vec<String> = (0..layout.edges.len())
+        .filter(|&k| layout.edges[k].highlighted)
+        .map(|k| k.to_string())
+        .collect();
+    if !edges.is_empty() {
+        writeln!(f, "    linkStyle {} {}", edges.join(","), HIGHLIGHT_STYLE)?;
+    }
+    Ok(())
+}
+
+fn write_plantuml(f: &mut fmt::Formatter, layout: &Layout) -> fmt::Result {
+    let node = |f: &mut fmt::Formatter, i: usize, indent: &str| {
+        let label = escape_plantuml(&layout.labels[i]);
+        write!(f, "{}rectangle \"{}\" as {}", indent, label, layout.ids[i])?;
+        if layout.highlighted_nodes[i] {
+            write!(f, " #line:red;line.bold")?;
+        }
+        writeln!(f)
+    };
+    writeln!(f, "@startuml")?;
+    for group in &layout.groups {
+        let label = escape_plantuml(&group.label);
+        writeln!(f, "package \"{}\" as {} {{", label, group.id)?;
+        for &i in &group.nodes {
+            node(f, i, "  ")?;
+        }
+        writeln!(f, "}}")?;
+    }
+    for &i in &layout.ungrouped {
+        node(f, i, "")?;
+    }
+    for edge in &layout.edges {
+        let arrow = if edge.highlighted {
+            "-[#red,bold]->"
+        } else {
+            "-->"
+        };
+        write!(
+            f,
+            "{} {} {}",
+            layout.ids[edge.from], arrow, layout.ids[edge.to]
+        )?;
+        if let Some(label) = &edge.label {
+            write!(f, " : {}", escape_plantuml(label))?;
+        }
+        writeln!(f)?;
+    }
+    writeln!(f, "@enduml")
+}
+
+// Words that cannot be used as IDs in either format.
+const KEYWORDS: [&str; 12] = [
+    "end",
+    "graph",
+    "flowchart",
+    "subgraph",
+    "direction",
+    "style",
+    "class",
+    "classdef",
+    "linkstyle",
+    "click",
+    "default",
+    "as",
+];
+
+// An ID for a node or group, distinct from all IDs in `used`, which it is added to.
+fn unique_id(label: &str, used: &mut HashSet<String>) -> String {
+    let mut id: String = label
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    if id.is_empty()
+        || id.starts_with(|c: char| c.is_ascii_digit())
+        || KEYWORDS.contains(&id.to_ascii_lowercase().as_str())
+    {
+        id.insert_str(0, "n_");
+    }
+    let mut unique = id.clone();
+    let mut n = 1;
+    while used.contains(&unique) {
+        n += 1;
+        unique = format!("{}_{}", id, n);
+    }
+    used.insert(unique.clone());
+    unique
+}
+
+// Escape a label for use inside double quotes, with Mermaid's entity codes.
+fn escape_mermaid(s: &str) -> String {
+    let mut result = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '#' => result.push_str("#35;"),
+            '"' => result.push_str("#quot;"),
+            '<' => result.push_str("#lt;"),
+            '>' => result.push_str("#gt;"),
+            '\n' => result.push_str("<br>"),
+            '\r' => {}
+            c => result.push(c),
+        }
+    }
+    result
+}
+
+// Escape a label for PlantUML, which has no escape for '"' but understands character
+// references.
+fn escape_plantuml(s: &str) -> String {
+    let mut result = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' => result.push_str("&#34;"),
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            '\r' => {}
+            c => result.push(c),
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    // A (group, name) pair.
+    type Module = (&'static str, &'static str);
+
+    // core/parse -> core/lex, and app -> core/parse, "end" and util/lex, a second node
+    // named lex.
+    fn graph() -> (DirectedGraph<Module>, Vec<NodeRef<Module>>) {
+        let mut graph = DirectedGraph::default();
+        let nodes: Vec<_> = [
+            ("core", "parse"),
+            ("core", "lex"),
+            ("", "app \"main\""),
+            ("", "end"),
+            ("util", "lex"),
+        ]
+        .iter()
+        .map(|&data| graph.add_node(data))
+        .collect();
+        graph.add_edge(&nodes[0], &nodes[1]);
+        graph.add_edge(&nodes[2], &nodes[0]);
+        graph.add_edge(&nodes[2], &nodes[3]);
+        graph.add_edge(&nodes[2], &nodes[4]);
+        (graph, nodes)
+    }
+
+    fn group(&(group, _): &(&str, &str)) -> Option<String> {
+        Some(group.to_string()).filter(|group| !group.is_empty())
+    }
+
+    #[test]
+    fn test_to_mermaid() {
+        let (graph, nodes) = graph();
+        let mermaid = graph
+            .to_mermaid(|&(_, name)| name.to_string())
+            .group_by(group)
+            .edge_label(|_, _, &(_, to)| Some(to).filter(|&to| to == "end").map(|_| "#1".into()))
+            .highlight_path(&[nodes[2].clone(), nodes[0].clone(), nodes[1].clone()])
+            .to_string();
+        assert_eq!(
+            mermaid,
+            "flowchart TD\n\
+             \x20   subgraph core[\"core\"]\n\
+             \x20       parse[\"parse\"]\n\
+             \x20       lex[\"lex\"]\n\
+             \x20   end\n\
+             \x20   subgraph util[\"util\"]\n\
+             \x20       lex_2[\"lex\"]\n\
+             \x20   end\n\
+             \x20   app__main_[\"app #quot;main#quot;\"]\n\
+             \x20   n_end[\"end\"]\n\
+             \x20   parse --> lex\n\
+             \x20   app__main_ --> parse\n\
+             \x20   app__main_ -->|\"#35;1\"| n_end\n\
+             \x20   app__main_ --> lex_2\n\
+             \x20   classDef highlighted stroke:#d00,stroke-width:3px\n\
+             \x20   class parse,lex,app__main_ highlighted\n\
+             \x20   linkStyle 0,1 stroke:#d00,stroke-width:3px\n"
+        );
+    }
+
+    #[test]
+    fn test_to_plantuml() {
+        let (graph, nodes) = graph();
+        // Nodes that are not in the graph are ignored.
+        let other = DirectedGraph::default().add_node(("", "other"));
+        let plantuml = graph
+            .to_plantuml(|&(_, name)| name.to_string())
+            .group_by(group)
+            .edge_label(|_, _, &(_, to)| Some(to).filter(|&to| to == "end").map(|_| "last".into()))
+            .highlight_path(&[nodes[2].clone(), nodes[3].clone(), other])
+            .to_string();
+        assert_eq!(
+            plantuml,
+            "@startuml\n\
+             package \"core\" as core {\n\
+             \x20 rectangle \"parse\" as parse\n\
+             \x20 rectangle \"lex\" as lex\n\
+             }\n\
+             package \"util\" as util {\n\
+             \x20 rectangle \"lex\" as lex_2\n\
+             }\n\
+             rectangle \"app &#34;main&#34;\" as app__main_ #line:red;line.bold\n\
+             rectangle \"end\" as n_end #line:red;line.bold\n\
+             parse --> lex\n\
+             app__main_ --> parse\n\
+             app__main_ -[#red,bold]-> n_end : last\n\
+             app__main_ --> lex_2\n\
+             @enduml\n"
+        );
+    }
+}
@@ -0,0 +1,1053 @@
+// Graphviz DOT output and input for DirectedGraph.
+//
+// On output, nodes are named n0, n1, ... by their position in DirectedGraph::nodes, so
+// names are always valid DOT identifiers whatever the data; what is shown is set by
+// the label.
+//
+// On input, any `digraph` is accepted: node, edge and attribute statements, default
+// attributes (scoped to the subgraph they appear in), subgraphs and edge chains. Ports
+// and graph attributes are parsed but ignored. Undirected graphs and edges are
+// rejected, as they cannot be represented.
+
+use crate::{DirectedGraph, NodeRef};
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fmt;
+
+// The attributes of a node or edge, as (name, value) pairs. Used by the DOT, GraphML
+// and GEXF formats; names and values are quoted and escaped on output.
+pub type Attributes = Vec<(String, String)>;
+
+type NodeAttributes<'a, T> = Box<dyn Fn(&T) -> Attributes + 'a>;
+type EdgeAttributes<'a, T, E> = Box<dyn Fn(&T, &E, &T) -> Attributes + 'a>;
+
+// A DOT rendering of a graph, created by DirectedGraph::to_dot. It implements Display,
+// so the text can be obtained with to_string() or written with write!.
+pub struct Dot<'a, T, E = ()> {
+    graph: &'a DirectedGraph<T, E>,
+    node_label: Box<dyn Fn(&T) -> String + 'a>,
+    node_attributes: Option<NodeAttributes<'a, T>>,
+    edge_attributes: Option<EdgeAttributes<'a, T, E>>,
+    highlighted: HashSet<NodeRef<T, E>>,
+    rank_by_layer: bool,
+}
+
+impl<T, E> DirectedGraph<T, E> {
+    // Render the graph in the DOT language, labelling every node with `node_label`. The
+    // rendering can be customized further through the methods of Dot.
+    pub fn to_dot<'a, F>(&'a self, node_label: F) -> Dot<'a, T, E>
+    where
+        F: Fn(&T) -> String + 'a,
+    {
+        Dot {
+            graph: self,
+            node_label: Box::new(node_label),
+            node_attributes: None,
+            edge_attributes: None,
+            highlighted: HashSet::new(),
+            rank_by_layer: false,
+        }
+    }
+}
+
+impl<'a, T, E> Dot<'a, T, E> {
+    // Give every node the attributes returned by `f`, e.g. ("shape", "box").
+    pub fn node_attributes<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&T) -> Attributes + 'a,
+    {
+        self.node_attributes = Some(Box::new(f));
+        self
+    }
+
+    // Give every edge the attributes returned by `f` for the data of its source, the
+    // edge itself and its target.
+    pub fn edge_attributes<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&T, &E, &T) -> Attributes + 'a,
+    {
+        self.edge_attributes = Some(Box::new(f));
+        self
+    }
+
+    // Draw `nodes`, and every edge between two of them, in red and bold, e.g. to show a
+    // cycle from a CycleError or a critical path. This overrides the color and penwidth
+    // given by node_attributes and edge_attributes.
+    pub fn highlight(mut self, nodes: &[NodeRef<T, E>]) -> Self {
+        self.highlighted.extend(nodes.iter().cloned());
+        self
+    }
+
+    // Put the nodes of each topological layer (see DirectedGraph::topological_layers) on
+    // the same rank. Ignored if the graph has a cycle.
+    pub fn rank_by_layer(mut self, rank_by_layer: bool) -> Self {
+        self.rank_by_layer = rank_by_layer;
+        self
+    }
+}
+
+impl<T, E> fmt::Display for Dot<'_, T, E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let indices = self.graph.node_indices();
+        writeln!(f, "digraph {{")?;
+        for (i, node) in self.graph.nodes.iter().enumerate() {
+            let data = &node.ptr.borrow().data;
+            let mut attributes = vec![("label".to_string(), (self.node_label)(data))];
+            if let Some(node_attributes) = &self.node_attributes {
+                attributes.extend(node_attributes(data));
+            }
+            if self.highlighted.contains(node) {
+                attributes.extend(highlight());
+            }
+            writeln!(f, "    n{} {};", i, AttributeList(&attributes))?;
+        }
+        for (i, node) in self.graph.nodes.iter().enumerate() {
+            let from_highlighted = self.highlighted.contains(node);
+            let node = node.ptr.borrow();
+            for (m, e) in node.outgoing_edges() {
+                let mut attributes = match &self.edge_attributes {
+                    Some(edge_attributes) => edge_attributes(&node.data, e, &m.ptr.borrow().data),
+                    None => Vec::new(),
+                };
+                if from_highlighted && self.highlighted.contains(m) {
+                    attributes.extend(highlight());
+                }
+                write!(f, "    n{} -> n{}", i, indices[m])?;
+                if !attributes.is_empty() {
+                    write!(f, " {}", AttributeList(&attributes))?;
+                }
+                writeln!(f, ";")?;
+            }
+        }
+        if self.rank_by_layer {
+            if let Ok(layers) = self.graph.topological_layers() {
+                for layer in layers {
+                    write!(f, "    {{ rank=same;")?;
+                    for node in &layer {
+                        write!(f, " n{};", indices[node])?;
+                    }
+                    writeln!(f, " }}")?;
+                }
+            }
+        }
+        writeln!(f, "}}")
+    }
+}
+
+// The attributes added to highlighted nodes and edges.
+fn highlight() -> Attributes {
+    vec![
+        ("color".to_string(), "red".to_string()),
+        ("penwidth".to_string(), "2".to_string()),
+    ]
+}
+
+// Formats as ["name"="value", ...].
+struct AttributeList<'a>(&'a [(String, String)]);
+
+impl fmt::Display for AttributeList<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "[")?;
+        for (i, (name, value)) in self.0.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "\"{}\"=\"{}\"", escape(name), escape(value))?;
+        }
+        write!(f, "]")
+    }
+}
+
+// Escape a string for use inside double quotes. Line breaks become \n, which DOT
+// renders as a centered line break. Every backslash is doubled, so \\ always stands
+// for one backslash and a backslash at the end is not read as escaping the closing
+// quote.
+fn escape(s: &str) -> String {
+    let mut result = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            '\r' => {}
+            c => result.push(c),
+        }
+    }
+    result
+}
+
+impl<T> DirectedGraph<T> {
+    // Read a graph from the DOT language. `node` is called once for every node, in order
+    // of first appearance, with its ID and its attributes; edges keep their order in
+    // the input.
+    pub fn from_dot<F>(input: &str, node: F) -> Result<Self, DotError>
+    where
+        F: FnMut(&str, &Attributes) -> T,
+    {
+        DirectedGraph::from_dot_with_edges(input, node, |_| ())
+    }
+}
+
+impl<T, E> DirectedGraph<T, E> {
+    // Like from_dot, also creating the data of every edge from its attributes.
+    pub fn from_dot_with_edges<F, G>(
+        input: &str,
+        mut node: F,
+        mut edge: G,
+    ) -> Result<Self, DotError>
+    where
+        F: FnMut(&str, &Attributes) -> T,
+        G: FnMut(&Attributes) -> E,
+    {
+        let parsed = Parser::new(input)?.parse_graph()?;
+        let mut graph = DirectedGraph::new();
+        let nodes: Vec<_> = parsed
+            .nodes
+            .iter()
+            .map(|(id, attributes)| graph.add_node(node(id, attributes)))
+            .collect();
+        for (from, to, attributes) in &parsed.edges {
+            graph.add_edge_with(&nodes[*from], &nodes[*to], edge(attributes));
+        }
+        Ok(graph)
+    }
+}
+
+// The error returned for malformed DOT input. Lines and columns start at 1, and columns
+// count characters.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DotError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for DotError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "line {}, column {}: {}",
+            self.line, self.column, self.message
+        )
+    }
+}
+
+impl Error for DotError {}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    // An identifier or number, which may also be a keyword.
+    Id(String),
+    // A double-quoted string, without the quotes.
+    Quoted(String),
+    // An HTML string, without the outer angle brackets.
+    Html(String),
+    LBrace,
+    RBrace,
+    LBracket,
+    RBracket,
+    Semicolon,
+    Comma,
+    Equals,
+    Colon,
+    Plus,
+    Arrow,
+    UndirectedEdge,
+    Eof,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Token::Id(s) => write!(f, "'{}'", s),
+            Token::Quoted(s) => write!(f, "\"{}\"", s),
+            Token::Html(s) => write!(f, "<{}>", s),
+            Token::LBrace => write!(f, "'{{'"),
+            Token::RBrace => write!(f, "'}}'"),
+            Token::LBracket => write!(f, "'['"),
+            Token::RBracket => write!(f, "']'"),
+            Token::Semicolon => write!(f, "';'"),
+            Token::Comma => write!(f, "','"),
+            Token::Equals => write!(f, "'='"),
+            Token::Colon => write!(f, "':'"),
+            Token::Plus => write!(f, "'+'"),
+            Token::Arrow => write!(f, "'->'"),
+            Token::UndirectedEdge => write!(f, "'--'"),
+            Token::Eof => write!(f, "end of input"),
+        }
+    }
+}
+
+// A token with the line and column where it starts.
+struct Spanned {
+    token: Token,
+    line: usize,
+    column: usize,
+}
+
+struct Lexer {
+    chars: Vec<char>,
+    pos: usize,
+    line: usize,
+    column: usize,
+    // Whether only whitespace precedes the current position on its line, in which case
+    // a '#' starts a (preprocessor output) line to be ignored.
+    line_blank: bool,
+}
+
+impl Lexer {
+    fn peek(&self, ahead: usize) -> Option<char> {
+        self.chars.get(self.pos + ahead).copied()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek(0)?;
+        self.pos += 1;
+        if c == '\n' {
+            self.line += 1;
+            self.column = 1;
+            self.line_blank = true;
+        } else {
+            self.column += 1;
+            self.line_blank &= c.is_whitespace();
+        }
+        Some(c)
+    }
+
+    fn error(&self, line: usize, column: usize, message: String) -> DotError {
+        DotError {
+            line,
+            column,
+            message,
+        }
+    }
+
+    fn tokenize(mut self) -> Result<Vec<Spanned>, DotError> {
+        let mut tokens = Vec::new();
+        loop {
+            self.skip_blanks()?;
+            let (line, column) = (self.line, self.column);
+            let token = match self.peek(0) {
+                None => Token::Eof,
+                Some(c) => self.token(c, line, column)?,
+            };
+            let eof = token == Token::Eof;
+            tokens.push(Spanned {
+                token,
+                line,
+                column,
+            });
+            if eof {
+                return Ok(tokens);
+            }
+        }
+    }
+
+    // Skip whitespace and comments.
+    fn skip_blanks(&mut self) -> Result<(), DotError> {
+        loop {
+            match (self.peek(0), self.peek(1)) {
+                (Some(c), _) if c.is_whitespace() => {
+                    self.bump();
+                }
+                (Some('#'), _) if self.line_blank => self.skip_line(),
+                (Some('/'), Some('/')) => self.skip_line(),
+                (Some('/'), Some('*')) => {
+                    let (line, column) = (self.line, self.column);
+                    self.bump();
+                    self.bump();
+                    while (self.peek(0), self.peek(1)) != (Some('*'), Some('/')) {
+                        if self.bump().is_none() {
+                            return Err(self.error(line, column, "unterminated comment".into()));
+                        }
+                    }
+                    self.bump();
+                    self.bump();
+                }
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    fn skip_line(&mut self) {
+        while matches!(self.peek(0), Some(c) if c != '\n') {
+            self.bump();
+        }
+    }
+
+    fn token(&mut self, c: char, line: usize, column: usize) -> Result<Token, DotError> {
+        let single = match c {
+            '{' => Some(Token::LBrace),
+            '}' => Some(Token::RBrace),
+            '[' => Some(Token::LBracket),
+            ']' => Some(Token::RBracket),
+            ';' => Some(Token::Semicolon),
+            ',' => Some(Token::Comma),
+            '=' => Some(Token::Equals),
+            ':' => Some(Token::Colon),
+            '+' => Some(Token::Plus),
+            _ => None,
+        };
+        if let Some(token) = single {
+            self.bump();
+            return Ok(token);
+        }
+        match (c, self.peek(1)) {
+            ('-', Some('>')) => {
+                self.bump();
+                self.bump();
+                Ok(Token::Arrow)
+            }
+            ('-', Some('-')) => {
+                self.bump();
+                self.bump();
+                Ok(Token::UndirectedEdge)
+            }
+            ('-', _) | ('.', _) | ('0'..='9', _) => self.number(line, column),
+            ('"', _) => self.quoted(line, column),
+            ('<', _) => self.html(line, column),
+            (c, _) if c.is_alphabetic() || c == '_' || !c.is_ascii() => {
+                let mut id = String::new();
+                while let Some(c) = self.peek(0) {
+                    if !(c.is_alphanumeric() || c == '_' || !c.is_ascii()) {
+                        break;
+                    }
+                    id.push(c);
+                    self.bump();
+                }
+                Ok(Token::Id(id))
+            }
+            (c, _) => Err(self.error(line, column, format!("unexpected character '{}'", c))),
+        }
+    }
+
+    // [-]?(.[0-9]+ | [0-9]+(.[0-9]*)?)
+    fn number(&mut self, line: usize, column: usize) -> Result<Token, DotError> {
+        let mut number = String::new();
+        if self.peek(0) == Some('-') {
+            number.push('-');
+            self.bump();
+        }
+        let (mut digits, mut dots) = (0, 0);
+        while let Some(c) = self.peek(0) {
+            match c {
+                '0'..='9' => digits += 1,
+                '.' if dots == 0 => dots += 1,
+                _ => break,
+            }
+            number.push(c);
+            self.bump();
+        }
+        if digits == 0 {
+            return Err(self.error(line, column, format!("invalid number '{}'", number)));
+        }
+        Ok(Token::Id(number))
+    }
+
+    // The escape sequences are the ones written by `escape`: \", \\ and \n stand for a
+    // quote, a backslash and a line break. A backslash before a line break joins the
+    // lines, and any other backslash is kept as is, so that Graphviz escapes such as \l
+    // reach the caller unchanged.
+    fn quoted(&mut self, line: usize, column: usize) -> Result<Token, DotError> {
+        self.bump();
+        let mut text = String::new();
+        loop {
+            match self.bump() {
+                None => return Err(self.error(line, column, "unterminated string".into())),
+                Some('"') => return Ok(Token::Quoted(text)),
+                Some('\\') => match self.peek(0) {
+                    Some(c @ ('"' | '\\')) => {
+                        self.bump();
+                        text.push(c);
+                    }
+                    Some('n') => {
+                        self.bump();
+                        text.push('\n');
+                    }
+                    Some('\n') => {
+                        self.bump();
+                    }
+                    _ => text.push('\\'),
+                },
+                Some(c) => text.push(c),
+            }
+        }
+    }
+
+    // An HTML string runs to the matching '>'.
+    fn html(&mut self, line: usize, column: usize) -> Result<Token, DotError> {
+        self.bump();
+        let mut text = String::new();
+        let mut depth = 1;
+        loop {
+            let c = match self.bump() {
+                Some(c) => c,
+                None => return Err(self.error(line, column, "unterminated HTML string".into())),
+            };
+            match c {
+                '<' => depth += 1,
+                '>' if depth == 1 => return Ok(Token::Html(text)),
+                '>' => depth -= 1,
+                _ => {}
+            }
+            text.push(c);
+        }
+    }
+}
+
+// Set an attribute, replacing an earlier value.
+pub(crate) fn set_attribute(attributes: &mut Attributes, name: String, value: String) {
+    match attributes.iter_mut().find(|(n, _)| *n == name) {
+        Some((_, v)) => *v = value,
+        None => attributes.push((name, value)),
+    }
+}
+
+// The default attributes in effect at some point of the input.
+#[derive(Clone, Default)]
+struct Scope {
+    node: Attributes,
+    edge: Attributes,
+}
+
+// The nodes and edges of a parsed graph, by index into `nodes`.
+#[derive(Default)]
+struct Parsed {
+    nodes: Vec<(String, Attributes)>,
+    edges: Vec<(usize, usize, Attributes)>,
+}
+
+struct Parser {
+    tokens: Vec<Spanned>,
+    pos: usize,
+    parsed: Parsed,
+    node_indices: HashMap<String, usize>,
+    // Set for a strict digraph, which merges parallel edges; maps every edge to its
+    // index in `parsed.edges`.
+    strict_edges: Option<HashMap<(usize, usize), usize>>,
+}
+
+impl Parser {
+    fn new(input: &str) -> Result<Self, DotError> {
+        let lexer = Lexer {
+            chars: input.chars().collect(),
+            pos: 0,
+            line: 1,
+            column: 1,
+            line_blank: true,
+        };
+        Ok(Parser {
+            tokens: lexer.tokenize()?,
+            pos: 0,
+            parsed: Parsed::default(),
+            node_indices: HashMap::new(),
+            strict_edges: None,
+        })
+    }
+
+    fn peek(&self) -> &Token {
+        &self.tokens[self.pos].token
+    }
+
+    fn advance(&mut self) -> Token {
+        let token = self.tokens[self.pos].token.clone();
+        if token != Token::Eof {
+            self.pos += 1;
+        }
+        token
+    }
+
+    // An error at the current token.
+    fn error(&self, message: String) -> DotError {
+        let spanned = &self.tokens[self.pos];
+        DotError {
+            line: spanned.line,
+            column: spanned.column,
+            message,
+        }
+    }
+
+    fn unexpected(&self, expected: &str) -> DotError {
+        match self.peek() {
+            Token::UndirectedEdge => {
+                self.error("undirected edges ('--') are not supported in a digraph".into())
+            }
+            found => self.error(format!("expected {}, found {}", expected, found)),
+        }
+    }
+
+    fn expect(&mut self, token: Token) -> Result<(), DotError> {
+        if *self.peek() == token {
+            self.advance();
+            Ok(())
+        } else {
+            Err(self.unexpected(&token.to_string()))
+        }
+    }
+
+    // Keywords are case-insensitive and never quoted.
+    fn at_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Token::Id(id) if id.eq_ignore_ascii_case(keyword))
+    }
+
+    fn at_any_keyword(&self) -> bool {
+        ["strict", "graph", "digraph", "node", "edge", "subgraph"]
+            .iter()
+            .any(|keyword| self.at_keyword(keyword))
+    }
+
+    fn at_id(&self) -> bool {
+        match self.peek() {
+            Token::Id(_) => !self.at_any_keyword(),
+            Token::Quoted(_) | Token::Html(_) => true,
+            _ => false,
+        }
+    }
+
+    // An ID; double-quoted strings can be concatenated with '+'.
+    fn parse_id(&mut self) -> Result<String, DotError> {
+        if !self.at_id() {
+            return Err(self.unexpected("an ID"));
+        }
+        match self.advance() {
+            Token::Quoted(mut text) => {
+                while *self.peek() == Token::Plus {
+                    self.advance();
+                    match self.peek() {
+                        Token::Quoted(more) => text.push_str(more),
+                        _ => return Err(self.unexpected("a double-quoted string")),
+                    }
+                    self.advance();
+                }
+                Ok(text)
+            }
+            Token::Id(text) | Token::Html(text) => Ok(text),
+            _ => unreachable!(),
+        }
+    }
+
+    // graph: [strict] digraph [ID] '{' stmt_list '}'
+    fn parse_graph(mut self) -> Result<Parsed, DotError> {
+        if self.at_keyword("strict") {
+            self.advance();
+            self.strict_edges = Some(HashMap::new());
+        }
+        if self.at_keyword("graph") {
+            return Err(self.error("undirected graphs are not supported".into()));
+        }
+        if !self.at_keyword("digraph") {
+            return Err(self.unexpected("'digraph'"));
+        }
+        self.advance();
+        if self.at_id() {
+            self.parse_id()?;
+        }
+        self.expect(Token::LBrace)?;
+        self.parse_stmt_list(&Scope::default())?;
+        self.expect(Token::RBrace)?;
+        if *self.peek() != Token::Eof {
+            return Err(self.unexpected("end of input"));
+        }
+        Ok(self.parsed)
+    }
+
+    // Parse statements up to the closing '}'. Returns the nodes they mention, in order of
+    // first mention.
+    fn parse_stmt_list(&mut self, outer: &Scope) -> Result<Vec<usize>, DotError> {
+        let mut scope = outer.clone();
+        let mut mentioned = Vec::new();
+        loop {
+            match self.peek() {
+                Token::RBrace => break,
+                Token::Eof => return Err(self.unexpected("'}'")),
+                Token::Semicolon => {
+                    self.advance();
+                }
+                _ => self.parse_stmt(&mut scope, &mut mentioned)?,
+            }
+        }
+        let mut seen = HashSet::new();
+        mentioned.retain(|&i| seen.insert(i));
+        Ok(mentioned)
+    }
+
+    fn parse_stmt(
+        &mut self,
+        scope: &mut Scope,
+        mentioned: &mut Vec<usize>,
+    ) -> Result<(), DotError> {
+        // attr_stmt: (graph | node | edge) attr_list
+        for keyword in ["graph", "node", "edge"] {
+            if self.at_keyword(keyword) {
+                self.advance();
+                if *self.peek() != Token::LBracket {
+                    return Err(self.unexpected("'['"));
+                }
+                let attributes = self.parse_attr_lists()?;
+                let defaults = match keyword {
+                    "node" => &mut scope.node,
+                    "edge" => &mut scope.edge,
+                    _ => return Ok(()),
+                };
+                for (name, value) in attributes {
+                    set_attribute(defaults, name, value);
+                }
+                return Ok(());
+            }
+        }
+        let first = if self.at_keyword("subgraph") || *self.peek() == Token::LBrace {
+            self.parse_subgraph(scope)?
+        } else if self.at_id() {
+            let id = self.parse_id()?;
+            // ID '=' ID, a graph attribute
+            if *self.peek() == Token::Equals {
+                self.advance();
+                self.parse_id()?;
+                return Ok(());
+            }
+            self.parse_port()?;
+            let node = self.node(id, scope);
+            if *self.peek() != Token::Arrow {
+                // node_stmt: node_id [attr_list]
+                for (name, value) in self.parse_attr_lists()? {
+                    set_attribute(&mut self.parsed.nodes[node].1, name, value);
+                }
+                mentioned.push(node);
+                return Ok(());
+            }
+            vec![node]
+        } else {
+            return Err(self.unexpected("a statement"));
+        };
+        // edge_stmt: (node_id | subgraph) ('->' (node_id | subgraph))+ [attr_list], or
+        // a lone subgraph
+        let mut groups = vec![first];
+        while *self.peek() == Token::Arrow {
+            self.advance();
+            let group = if self.at_keyword("subgraph") || *self.peek() == Token::LBrace {
+                self.parse_subgraph(scope)?
+            } else {
+                let id = self.parse_id()?;
+                self.parse_port()?;
+                vec![self.node(id, scope)]
+            };
+            groups.push(group);
+        }
+        let attributes = self.parse_attr_lists()?;
+        for pair in groups.windows(2) {
+            for &from in &pair[0] {
+                for &to in &pair[1] {
+                    self.add_edge(from, to, scope, &attributes);
+                }
+            }
+        }
+        mentioned.extend(groups.into_iter().flatten());
+        Ok(())
+    }
+
+    // subgraph: [subgraph [ID]] '{' stmt_list '}'. Returns the nodes it mentions.
+    fn parse_subgraph(&mut self, scope: &Scope) -> Result<Vec<usize>, DotError> {
+        if self.at_keyword("subgraph") {
+            self.advance();
+            if self.at_id() {
+                self.parse_id()?;
+            }
+        }
+        self.expect(Token::LBrace)?;
+        let nodes = self.parse_stmt_list(scope)?;
+        self.expect(Token::RBrace)?;
+        Ok(nodes)
+    }
+
+    // port: ':' ID [':' ID], ignored.
+    fn parse_port(&mut self) -> Result<(), DotError> {
+        for _ in 0..2 {
+            if *self.peek() != Token::Colon {
+                break;
+            }
+            self.advance();
+            self.parse_id()?;
+        }
+        Ok(())
+    }
+
+    // attr_list: ('[' [ID '=' ID [';' | ',']]* ']')*, possibly empty.
+    fn parse_attr_lists(&mut self) -> Result<Attributes, DotError> {
+        let mut attributes = Attributes::new();
+        while *self.peek() == Token::LBracket {
+            self.advance();
+            while *self.peek() != Token::RBracket {
+                let name = self.parse_id()?;
+                self.expect(Token::Equals)?;
+                let value = self.parse_id()?;
+                set_attribute(&mut attributes, name, value);
+                if let Token::Semicolon | Token::Comma = self.peek() {
+                    self.advance();
+                }
+            }
+            self.advance();
+        }
+        Ok(attributes)
+    }
+
+    // The index of the node with the given ID, creating it with the current default
+    // attributes if it is new.
+    fn node(&mut self, id: String, scope: &Scope) -> usize {
+        if let Some(&i) = self.node_indices.get(&id) {
+            return i;
+        }
+        let i = self.parsed.nodes.len();
+        self.node_indices.insert(id.clone(), i);
+        self.parsed.nodes.push((id, scope.node.clone()));
+        i
+    }
+
+    // Add an edge with the current default attributes, overridden by `attributes`. In
+    // a strict digraph, an existing edge only gets `attributes`.
+    fn add_edge(&mut self, from: usize, to: usize, scope: &Scope, attributes: &Attributes) {
+        let k = match &mut self.strict_edges {
+            Some(strict_edges) if strict_edges.contains_key(&(from, to)) => {
+                strict_edges[&(from, to)]
+            }
+            strict_edges => {
+                if let Some(strict_edges) = strict_edges {
+                    strict_edges.insert((from, to), self.parsed.edges.len());
+                }
+                self.parsed.edges.push((from, to, scope.edge.clone()));
+                self.parsed.edges.len() - 1
+            }
+        };
+        for (name, value) in attributes {
+            set_attribute(&mut self.parsed.edges[k].2, name.clone(), value.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    #[test]
+    fn test_to_dot() {
+        let mut graph = DirectedGraph::<&str, u32>::new();
+        let a = graph.add_node("a");
+        let b = graph.add_node("say \"b\"");
+        let c = graph.add_node("c");
+        graph.add_edge_with(&a, &b, 1);
+        graph.add_edge_with(&a, &c, 2);
+        graph.add_edge_with(&b, &c, 3);
+
+        let label = |s: &&str| s.to_string();
+        assert_eq!(
+            graph.to_dot(label).to_string(),
+            "digraph {\n\
+             \x20   n0 [\"label\"=\"a\"];\n\
+             \x20   n1 [\"label\"=\"say \\\"b\\\"\"];\n\
+             \x20   n2 [\"label\"=\"c\"];\n\
+             \x20   n0 -> n1;\n\
+             \x20   n0 -> n2;\n\
+             \x20   n1 -> n2;\n\
+             }\n"
+        );
+
+        let dot = graph
+            .to_dot(label)
+            .node_attributes(|_| vec![("shape".to_string(), "box".to_string())])
+            .edge_attributes(|_, w, _| vec![("weight".to_string(), w.to_string())])
+            .highlight(&[a.clone(), c.clone()])
+            .rank_by_layer(true)
+            .to_string();
+        let lines: Vec<_> = dot.lines().collect();
+        assert_eq!(
+            lines[1],
+            "    n0 [\"label\"=\"a\", \"shape\"=\"box\", \"color\"=\"red\", \"penwidth\"=\"2\"];"
+        );
+        assert_eq!(
+            lines[2],
+            "    n1 [\"label\"=\"say \\\"b\\\"\", \"shape\"=\"box\"];"
+        );
+        assert_eq!(lines[4], "    n0 -> n1 [\"weight\"=\"1\"];");
+        assert_eq!(
+            lines[5],
+            "    n0 -> n2 [\"weight\"=\"2\", \"color\"=\"red\", \"penwidth\"=\"2\"];"
+        );
+        assert_eq!(lines[7], "    { rank=same; n0; }");
+        assert_eq!(lines[8], "    { rank=same; n1; }");
+        assert_eq!(lines[9], "    { rank=same; n2; }");
+        assert_eq!(lines[10], "}");
+
+        // Names are escaped like values, and a trailing backslash is doubled.
+        let dot = graph
+            .to_dot(|s| format!("{}\\", s))
+            .node_attributes(|_| vec![("my \"key\"=".to_string(), "x".to_string())])
+            .to_string();
+        assert_eq!(
+            dot.lines().nth(1).unwrap(),
+            "    n0 [\"label\"=\"a\\\\\", \"my \\\"key\\\"=\"=\"x\"];"
+        );
+
+        // With a cycle, the ranks are left out.
+        graph.add_edge_with(&c, &a, 4);
+        let dot = graph.to_dot(label).rank_by_layer(true).to_string();
+        assert!(!dot.contains("rank"));
+        assert!(dot.contains("n2 -> n0;"));
+    }
+
+    fn edges<T: Clone, E: Clone>(graph: &DirectedGraph<T, E>) -> Vec<(T, T, E)> {
+        let mut edges = Vec::new();
+        for node in graph.nodes() {
+            let node = node.ptr.borrow();
+            for (m, e) in node.outgoing_edges() {
+                edges.push((node.data.clone(), m.ptr.borrow().data.clone(), e.clone()));
+            }
+        }
+        edges
+    }
+
+    #[test]
+    fn test_from_dot() {
+        let input = r#"
+            /* The build graph */
+            strict digraph "build" {
+                rankdir = LR;
+                node [shape=box];
+                edge [kind=build];
+                a [label="lib" + "core"]
+                a -> b -> c [kind=runtime];  // a chain
+                # preprocessor output
+                subgraph cluster_tests {
+                    node [color=red]
+                    t1; t2
+                }
+                {t1 t2} -> a:out:e
+                c -> "d" [weight=2, kind = <<b>dev</b>>]
+                b -> c [weight=3]
+            }
+        "#;
+        let graph = DirectedGraph::from_dot_with_edges(
+            input,
+            |id, attributes| {
+                let attributes: Vec<_> = attributes
+                    .iter()
+                    .map(|(name, value)| format!("{}={}", name, value))
+                    .collect();
+                format!("{}[{}]", id, attributes.join(","))
+            },
+            |attributes| attributes.clone(),
+        )
+        .unwrap();
+        let nodes: Vec<_> = graph
+            .nodes()
+            .iter()
+            .map(|n| n.ptr.borrow().data.clone())
+            .collect();
+        assert_eq!(
+            nodes,
+            vec![
+                "a[shape=box,label=libcore]",
+                "b[shape=box]",
+                "c[shape=box]",
+                "t1[shape=box,color=red]",
+                "t2[shape=box,color=red]",
+                "d[shape=box]",
+            ]
+        );
+        let edges: Vec<_> = edges(&graph)
+            .into_iter()
+            .map(|(from, to, attributes)| {
+                let attributes: Vec<_> =
+                    attributes.iter().map(|(_, value)| value.clone()).collect();
+                format!("{}{}:{}", &from[..1], &to[..1], attributes.join(","))
+            })
+            .collect();
+        // The strict graph merges the second b -> c into the first.
+        assert_eq!(
+            edges,
+            vec![
+                "ab:runtime",
+                "bc:runtime,3",
+                "cd:<b>dev</b>,2",
+                "ta:build",
+                "ta:build"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_to_dot_round_trip() {
+        let mut graph = DirectedGraph::<String, u32>::new();
+        let a = graph.add_node("a \"quoted\" name".to_string());
+        let b = graph.add_node("b".to_string());
+        let c = graph.add_node("C:\\dir\\".to_string());
+        let d = graph.add_node("\\\"\\n\\\nline\\l\\".to_string());
+        graph.add_edge_with(&a, &b, 7);
+        graph.add_edge_with(&b, &b, 8);
+        graph.add_edge_with(&b, &c, 9);
+        graph.add_edge_with(&c, &d, 10);
+        let dot = graph
+            .to_dot(|s| s.clone())
+            .edge_attributes(|_, w, _| vec![("weight".to_string(), w.to_string())])
+            .to_string();
+        let attribute = |attributes: &Attributes, name: &str| {
+            attributes
+                .iter()
+                .find(|(n, _)| n == name)
+                .unwrap()
+                .1
+                .clone()
+        };
+        let parsed = DirectedGraph::from_dot_with_edges(
+            &dot,
+            |_, attributes| attribute(attributes, "label"),
+            |attributes| attribute(attributes, "weight").parse().unwrap(),
+        )
+        .unwrap();
+        assert_eq!(edges(&parsed), edges(&graph));
+    }
+
+    #[test]
+    fn test_from_dot_errors() {
+        let error = |input: &str| {
+            let error = DirectedGraph::from_dot(input, |id, _| id.to_string())
+                .err()
+                .unwrap();
+            error.to_string()
+        };
+        assert_eq!(
+            error("graph { a -- b }"),
+            "line 1, column 1: undirected graphs are not supported"
+        );
+        assert_eq!(
+            error("digraph {\n    a -- b\n}"),
+            "line 2, column 7: undirected edges ('--') are not supported in a digraph"
+        );
+        assert_eq!(
+            error("digraph {\n    a -> [x=1]\n}"),
+            "line 2, column 10: expected an ID, found '['"
+        );
+        assert_eq!(
+            error("digraph {\n    a [label=\"oops]\n}"),
+            "line 2, column 14: unterminated string"
+        );
+        assert_eq!(
+            error("digraph { a [x] }"),
+            "line 1, column 15: expected '=', found ']'"
+        );
+        assert_eq!(
+            error("digraph { a -> b"),
+            "line 1, column 17: expected '}', found end of input"
+        );
+        assert_eq!(
+            error("digraph { a } b"),
+            "line 1, column 15: expected end of input, found 'b'"
+        );
+        assert_eq!(
+            error("digraph { node -> a }"),
+            "line 1, column 16: expected '[', found '->'"
+        );
+        assert_eq!(
+            error("digraph { a -> b; @ }"),
+            "line 1, column 19: unexpected character '@'"
+        );
+    }
+}
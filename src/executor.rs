@@ -0,0 +1,299 @@
+// Run a function on every node of a DirectedGraph on a pool of threads, starting each
+// node as soon as all of its predecessors (its `incoming` nodes) have completed.
+
+use crate::{CycleError, DirectedGraph, NodeRef};
+use std::any::Any;
+use std::collections::{HashMap, VecDeque};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Condvar, Mutex};
+use std::thread;
+
+// What to do once a node has failed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FailureMode {
+    // Start no further nodes; nodes that are already running are allowed to finish.
+    FailFast,
+    // Keep running every node that does not depend on a failed node.
+    KeepGoing,
+}
+
+// The outcome of one node of the graph.
+#[derive(Debug, PartialEq, Eq)]
+pub enum TaskOutcome<R, E> {
+    Succeeded(R),
+    Failed(E),
+    // The node was never started: one of its ancestors failed, or in FailFast mode,
+    // some other node failed first.
+    Skipped,
+    // The node was started but dropped before it completed, because some other node
+    // failed in FailFast mode. Only produced by AsyncExecutor; threads cannot be
+    // interrupted.
+    Cancelled,
+}
+impl<R, E> TaskOutcome<R, E> {
+    pub fn is_success(&self) -> bool {
+        matches!(self, TaskOutcome::Succeeded(_))
+    }
+}
+
+// The outcome of every node of a graph run by an executor. L is the type of the
+// graph's edge data.
+pub type Outcomes<T, R, E, L = ()> = HashMap<NodeRef<T, L>, TaskOutcome<R, E>>;
+
+pub struct Executor {
+    max_parallelism: usize,
+    failure_mode: FailureMode,
+}
+// By default, use as many threads as the machine has cores, and fail fast.
+impl Default for Executor {
+    fn default() -> Self {
+        let cores = thread::available_parallelism().map_or(1, |n| n.get());
+        Executor::new(cores, FailureMode::FailFast)
+    }
+}
+impl Executor {
+    // Create an executor running at most `max_parallelism` nodes at once (at least one).
+    pub fn new(max_parallelism: usize, failure_mode: FailureMode) -> Self {
+        Executor {
+            max_parallelism: max_parallelism.max(1),
+            failure_mode,
+        }
+    }
+
+    // Run `f` on the data of every node of `graph`, never starting a node before all of
+    // its predecessors have succeeded. Returns the outcome of every node, or an error
+    // without running anything if the graph has a cycle. If `f` panics, no further
+    // nodes are started and the panic is resumed on the calling thread.
+    pub fn run<T, L, R, E, F>(
+        &self,
+        graph: &DirectedGraph<T, L>,
+        f: F,
+    ) -> Result<Outcomes<T, R, E, L>, CycleError<T, L>>
+    where
+        T: Sync,
+        R: Send,
+        E: Send,
+        F: Fn(&T) -> Result<R, E> + Sync,
+    {
+        graph.topological_order()?;
+        let n = graph.nodes.len();
+        let (successors, pending) = adjacency(graph);
+        // Keep every node borrowed for the whole run, so that the workers can be handed
+        // plain references to the data.
+        let borrowed: Vec<_> = graph.nodes.iter().map(|node| node.ptr.borrow()).collect();
+        let data: Vec<&T> = borrowed.iter().map(|node| &node.data).collect();
+
+        let state = Mutex::new(State::new(pending));
+        let wakeup = Condvar::new();
+        let worker = || loop {
+            let i = {
+                let mut state = state.lock().unwrap();
+                loop {
+                    if state.stopped || state.finished == n {
+                        return;
+                    }
+                    if let Some(i) = state.ready.pop_front() {
+                        break i;
+                    }
+                    state = wakeup.wait(state).unwrap();
+                }
+            };
+            let result = panic::catch_unwind(AssertUnwindSafe(|| f(data[i])));
+            let mut state = state.lock().unwrap();
+            match result {
+                Ok(Ok(r)) => state.complete(i, TaskOutcome::Succeeded(r), &successors),
+                Ok(Err(e)) => {
+                    state.complete(i, TaskOutcome::Failed(e), &successors);
+                    if self.failure_mode == FailureMode::FailFast {
+                        state.stopped = true;
+                    }
+                }
+                Err(payload) => {
+                    state.panic = Some(payload);
+                    state.stopped = true;
+                }
+            }
+            wakeup.notify_all();
+        };
+        thread::scope(|scope| {
+            for _ in 1..self.max_parallelism.min(n) {
+                scope.spawn(worker);
+            }
+            worker();
+        });
+
+        let mut state = state.into_inner().unwrap();
+        if let Some(payload) = state.panic.take() {
+            panic::resume_unwind(payload);
+        }
+        Ok(state.into_outcomes(graph))
+    }
+}
+
+// The successors of every node and the number of its predecessors, by node index.
+pub(crate) fn adjacency<T, L>(graph: &DirectedGraph<T, L>) -> (Vec<Vec<usize>>, Vec<usize>) {
+    let indices = graph.node_indices();
+    graph
+        .nodes
+        .iter()
+        .map(|node| {
+            let node = node.ptr.borrow();
+            let successors = node.outgoing.iter().map(|m| indices[m]).collect();
+            (successors, node.incoming.len())
+        })
+        .unzip()
+}
+
+// The scheduling bookkeeping of Executor::run and AsyncExecutor::run: Kahn's algorithm
+// with a counter of unfinished predecessors per node. Nodes are identified by their
+// index in DirectedGraph::nodes.
+pub(crate) struct State<R, E> {
+    // Nodes that can be started right away.
+    pub(crate) ready: VecDeque<usize>,
+    // pending[i] is the number of predecessors of node i that have not finished yet.
+    pending: Vec<usize>,
+    // blocked[i] is set if some predecessor of node i did not succeed.
+    blocked: Vec<bool>,
+    pub(crate) outcomes: Vec<Option<TaskOutcome<R, E>>>,
+    // The number of nodes with an outcome.
+    pub(crate) finished: usize,
+    // Set when no further nodes should be started.
+    pub(crate) stopped: bool,
+    panic: Option<Box<dyn Any + Send>>,
+}
+impl<R, E> State<R, E> {
+    // `pending[i]` is the number of incoming edges of node i.
+    pub(crate) fn new(pending: Vec<usize>) -> Self {
+        let n = pending.len();
+        State {
+            ready: (0..n).filter(|&i| pending[i] == 0).collect(),
+            pending,
+            blocked: vec![false; n],
+            outcomes: (0..n).map(|_| None).collect(),
+            finished: 0,
+            stopped: false,
+            panic: None,
+        }
+    }
+
+    // Record the outcome of node i and release its successors. Successors of a node
+    // that did not succeed are skipped as soon as all of their predecessors finished.
+    pub(crate) fn complete(
+        &mut self,
+        i: usize,
+        outcome: TaskOutcome<R, E>,
+        successors: &[Vec<usize>],
+    ) {
+        let mut finished = vec![(i, outcome)];
+        while let Some((i, outcome)) = finished.pop() {
+            let success = outcome.is_success();
+            self.outcomes[i] = Some(outcome);
+            self.finished += 1;
+            for &j in &successors[i] {
+                self.pending[j] -= 1;
+                self.blocked[j] |= !success;
+                if self.pending[j] == 0 {
+                    if self.blocked[j] {
+                        finished.push((j, TaskOutcome::Skipped));
+                    } else {
+                        self.ready.push_back(j);
+                    }
+                }
+            }
+        }
+    }
+
+    // Key the outcomes by node. Nodes that never got an outcome were not started.
+    pub(crate) fn into_outcomes<T, L>(self, graph: &DirectedGraph<T, L>) -> Outcomes<T, R, E, L> {
+        graph
+            .nodes
+            .iter()
+            .cloned()
+            .zip(self.outcomes)
+            .map(|(node, outcome)| (node, outcome.unwrap_or(TaskOutcome::Skipped)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::executor::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    // A wide graph: 'a' -> each of 'b'..='k' -> 'l'.
+    fn fan_out_fan_in() -> (DirectedGraph<char>, Vec<NodeRef<char>>) {
+        let mut graph = DirectedGraph::default();
+        let nodes: Vec<_> = ('a'..='l').map(|c| graph.add_node(c)).collect();
+        for middle in &nodes[1..11] {
+            graph.add_edge(&nodes[0], middle);
+            graph.add_edge(middle, &nodes[11]);
+        }
+        (graph, nodes)
+    }
+
+    #[test]
+    fn test_runs_in_dependency_order() {
+        let (graph, nodes) = fan_out_fan_in();
+        let finished = Mutex::new(Vec::new());
+        let running = AtomicUsize::new(0);
+        let max_running = AtomicUsize::new(0);
+        let outcomes = Executor::new(3, FailureMode::FailFast)
+            .run(&graph, |&c| {
+                let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                max_running.fetch_max(now, Ordering::SeqCst);
+                thread::sleep(Duration::from_millis(5));
+                running.fetch_sub(1, Ordering::SeqCst);
+                finished.lock().unwrap().push(c);
+                Ok::<_, ()>(c.to_ascii_uppercase())
+            })
+            .ok()
+            .unwrap();
+
+        let finished = finished.into_inner().unwrap();
+        assert_eq!(finished.len(), 12);
+        assert_eq!(finished[0], 'a');
+        assert_eq!(finished[11], 'l');
+        assert!(max_running.load(Ordering::SeqCst) <= 3);
+        assert!(max_running.load(Ordering::SeqCst) > 1);
+        assert_eq!(outcomes[&nodes[4]], TaskOutcome::Succeeded('E'));
+    }
+
+    #[test]
+    fn test_failure_modes() {
+        let (graph, nodes) = fan_out_fan_in();
+        let f = |&c: &char| if c == 'c' { Err("boom") } else { Ok(c) };
+
+        // Keep going: everything but 'l', which depends on 'c', still runs.
+        let outcomes = Executor::new(4, FailureMode::KeepGoing)
+            .run(&graph, f)
+            .ok()
+            .unwrap();
+        assert_eq!(outcomes[&nodes[2]], TaskOutcome::Failed("boom"));
+        assert_eq!(outcomes[&nodes[11]], TaskOutcome::Skipped);
+        let succeeded = outcomes.values().filter(|o| o.is_success()).count();
+        assert_eq!(succeeded, 10);
+
+        // Fail fast with a single thread: nothing after 'c' is started.
+        let outcomes = Executor::new(1, FailureMode::FailFast)
+            .run(&graph, f)
+            .ok()
+            .unwrap();
+        assert_eq!(outcomes[&nodes[1]], TaskOutcome::Succeeded('b'));
+        assert_eq!(outcomes[&nodes[2]], TaskOutcome::Failed("boom"));
+        assert_eq!(outcomes[&nodes[3]], TaskOutcome::Skipped);
+    }
+
+    #[test]
+    fn test_cycle_is_rejected() {
+        let (mut graph, nodes) = fan_out_fan_in();
+        graph.add_edge(&nodes[11], &nodes[0]);
+        let ran = AtomicUsize::new(0);
+        let result = Executor::default().run(&graph, |_| {
+            ran.fetch_add(1, Ordering::SeqCst);
+            Ok::<_, ()>(())
+        });
+        assert!(result.is_err());
+        assert_eq!(ran.load(Ordering::SeqCst), 0);
+    }
+}
@@ -0,0 +1,398 @@
+// GEXF output and input for DirectedGraph, e.g. for Gephi.
+//
+// As with GraphML, node and edge data are mapped to and from attributes by closures,
+// and every attribute is declared as a string. An attribute named "label" is written
+// as the built-in label of the node or edge, which is what Gephi displays; all others
+// become attribute values. When reading, the built-in label and weight are returned as
+// attributes of those names. Nodes and edges are written in the same order as for
+// GraphML.
+
+use crate::dot::set_attribute;
+use crate::xml::{self, attribute_names, escape, Element, XmlError};
+use crate::{Attributes, DirectedGraph};
+use std::collections::HashMap;
+use std::fmt::Write;
+
+impl<T, E> DirectedGraph<T, E> {
+    // Write the graph as a GEXF 1.3 document, with the attributes returned by
+    // `node_attributes` and `edge_attributes` for the data of every node and edge.
+    pub fn to_gexf<F, G>(&self, node_attributes: F, edge_attributes: G) -> String
+    where
+        F: Fn(&T) -> Attributes,
+        G: Fn(&E) -> Attributes,
+    {
+        let indices = self.node_indices();
+        let nodes: Vec<Attributes> = self
+            .nodes
+            .iter()
+            .map(|node| node_attributes(&node.ptr.borrow().data))
+            .collect();
+        let mut edges = Vec::new();
+        let mut edge_data = Vec::new();
+        for (i, node) in self.nodes.iter().enumerate() {
+            for (m, e) in node.ptr.borrow().outgoing_edges() {
+                edges.push(format!("source=\"n{}\" target=\"n{}\"", i, indices[m]));
+                edge_data.push(edge_attributes(e));
+            }
+        }
+
+        let mut out = String::new();
+        writeln!(out, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>").unwrap();
+        writeln!(out, "<gexf xmlns=\"http://gexf.net/1.3\" version=\"1.3\">").unwrap();
+        writeln!(out, "  <graph defaultedgetype=\"directed\">").unwrap();
+        // Attribute IDs 0, 1, ... per class, in order of first appearance.
+        let mut ids = HashMap::new();
+        for (class, lists) in [("node", &nodes), ("edge", &edge_data)] {
+            let names: Vec<_> = attribute_names(lists)
+                .into_iter()
+                .filter(|&name| name != "label")
+                .collect();
+            if names.is_empty() {
+                continue;
+            }
+            writeln!(out, "    <attributes class=\"{}\">", class).unwrap();
+            for (id, name) in names.into_iter().enumerate() {
+                writeln!(
+                    out,
+                    "      <attribute id=\"{}\" title=\"{}\" type=\"string\"/>",
+                    id,
+                    escape(name)
+                )
+                .unwrap();
+                ids.insert((class, name), id);
+            }
+            writeln!(out, "    </attributes>").unwrap();
+        }
+        let write_element =
+            |out: &mut String, class: &str, tag: String, attributes: &Attributes| {
+                let label = attributes.iter().find(|(name, _)| name == "label");
+                let tag = match label {
+                    Some((_, label)) => format!("{} label=\"{}\"", tag, escape(label)),
+                    None => tag,
+                };
+                if attributes.len() == label.iter().count() {
+                    writeln!(out, "      <{}/>", tag).unwrap();
+                    return;
+                }
+                writeln!(out, "      <{}>", tag).unwrap();
+                writeln!(out, "        <attvalues>").unwrap();
+                for (name, value) in attributes {
+                    if name != "label" {
+                        writeln!(
+                            out,
+                            "          <attvalue for=\"{}\" value=\"{}\"/>",
+                            ids[&(class, name.as_str())],
+                            escape(value)
+                        )
+                        .unwrap();
+                    }
+                }
+                writeln!(out, "        </attvalues>").unwrap();
+                writeln!(out, "      </{}>", class).unwrap();
+            };
+        writeln!(out, "    <nodes>").unwrap();
+        for (i, attributes) in nodes.iter().enumerate() {
+            write_element(&mut out, "node", format!("node id=\"n{}\"", i), attributes);
+        }
+        writeln!(out, "    </nodes>").unwrap();
+        writeln!(out, "    <edges>").unwrap();
+        for (k, (endpoints, attributes)) in edges.iter().zip(&edge_data).enumerate() {
+            let tag = format!("edge id=\"e{}\" {}", k, endpoints);
+            write_element(&mut out, "edge", tag, attributes);
+        }
+        writeln!(out, "    </edges>").unwrap();
+        writeln!(out, "  </graph>").unwrap();
+        writeln!(out, "</gexf>").unwrap();
+        out
+    }
+}
+
+impl<T> DirectedGraph<T> {
+    // Read a graph from a GEXF document. `node` is called once for every node, in
+    // document order, with its ID and its attributes: its label, its attribute values
+    // by title, and the defaults of the attributes it has no value for.
+    pub fn from_gexf<F>(input: &str, node: F) -> Result<Self, XmlError>
+    where
+        F: FnMut(&str, &Attributes) -> T,
+    {
+        DirectedGraph::from_gexf_with_edges(input, node, |_| ())
+    }
+}
+
+impl<T, E> DirectedGraph<T, E> {
+    // Like from_gexf, also creating the data of every edge from its attributes: its
+    // label, its weight, and its attribute values as for nodes. Edges are added in
+    // document order. Undirected and mutual edges and nested nodes cannot be
+    // represented and are rejected.
+    pub fn from_gexf_with_edges<F, G>(
+        input: &str,
+        mut node: F,
+        mut edge: G,
+    ) -> Result<Self, XmlError>
+    where
+        F: FnMut(&str, &Attributes) -> T,
+        G: FnMut(&Attributes) -> E,
+    {
+        let root = xml::parse(input)?;
+        if root.name != "gexf" {
+            return Err(root.error(format!("expected a <gexf> document, found <{}>", root.name)));
+        }
+        let mut graphs = root.elements("graph");
+        let graph = graphs
+            .next()
+            .ok_or_else(|| root.error("the document contains no graph".into()))?;
+        if let Some(other) = graphs.next() {
+            return Err(other.error("documents with several graphs are not supported".into()));
+        }
+        let directed = |element: &Element, attribute| match element.attribute(attribute) {
+            None | Some("directed") => Ok(true),
+            Some("undirected") | Some("mutual") => Ok(false),
+            Some(other) => Err(element.error(format!("invalid {} '{}'", attribute, other))),
+        };
+        let default_directed = directed(graph, "defaultedgetype")?;
+        let node_declarations = Declarations::new(graph, "node")?;
+        let edge_declarations = Declarations::new(graph, "edge")?;
+
+        let mut result = DirectedGraph::new();
+        let mut nodes = HashMap::new();
+        for element in graph.elements("nodes").flat_map(|n| n.elements("node")) {
+            let id = element.required_attribute("id")?;
+            if let Some(nested) = element.elements("nodes").next() {
+                return Err(nested.error("nested nodes are not supported".into()));
+            }
+            if nodes.contains_key(id) {
+                return Err(element.error(format!("duplicate node ID '{}'", id)));
+            }
+            let attributes = node_declarations.attributes(element, &["label"])?;
+            nodes.insert(id, result.add_node(node(id, &attributes)));
+        }
+        for element in graph.elements("edges").flat_map(|e| e.elements("edge")) {
+            let edge_directed = match element.attribute("type") {
+                None => default_directed,
+                Some(_) => directed(element, "type")?,
+            };
+            if !edge_directed {
+                return Err(element.error("undirected edges cannot be represented".into()));
+            }
+            let endpoint = |name| {
+                let id = element.required_attribute(name)?;
+                nodes
+                    .get(id)
+                    .ok_or_else(|| element.error(format!("unknown node '{}'", id)))
+            };
+            let (from, to) = (endpoint("source")?, endpoint("target")?);
+            let attributes = edge_declarations.attributes(element, &["label", "weight"])?;
+            result.add_edge_with(from, to, edge(&attributes));
+        }
+        Ok(result)
+    }
+}
+
+// The <attribute> declarations of one class (node or edge) of a graph.
+struct Declarations<'a> {
+    class: &'a str,
+    // By attribute ID: the title and the default.
+    declarations: HashMap<&'a str, (&'a str, Option<String>)>,
+    // Attribute IDs in document order.
+    order: Vec<&'a str>,
+}
+
+impl<'a> Declarations<'a> {
+    fn new(graph: &'a Element, class: &'a str) -> Result<Self, XmlError> {
+        let mut declarations = HashMap::new();
+        let mut order = Vec::new();
+        let lists = graph
+            .elements("attributes")
+            .filter(|a| a.attribute("class") == Some(class));
+        for attribute in lists.flat_map(|a| a.elements("attribute")) {
+            let id = attribute.required_attribute("id")?;
+            let title = attribute.attribute("title").unwrap_or(id);
+            let default = attribute.elements("default").next().map(|d| d.text());
+            if declarations.insert(id, (title, default)).is_some() {
+                return Err(attribute.error(format!("duplicate attribute ID '{}'", id)));
+            }
+            order.push(id);
+        }
+        Ok(Declarations {
+            class,
+            declarations,
+            order,
+        })
+    }
+
+    // The attributes of a node or edge element: the given built-in XML attributes,
+    // then its attribute values and the defaults.
+    fn attributes(&self, element: &Element, built_in: &[&str]) -> Result<Attributes, XmlError> {
+        let mut attributes = Attributes::new();
+        for &name in built_in {
+            if let Some(value) = element.attribute(name) {
+                attributes.push((name.to_string(), value.to_string()));
+            }
+        }
+        for value in element
+            .elements("attvalues")
+            .flat_map(|a| a.elements("attvalue"))
+        {
+            // GEXF 1.1 used "id" where later versions use "for".
+            let id = match value.attribute("for") {
+                Some(id) => id,
+                None => value.required_attribute("id")?,
+            };
+            let title = match self.declarations.get(id) {
+                Some((title, _)) => title,
+                None => {
+                    return Err(value.error(format!("unknown {} attribute '{}'", self.class, id)))
+                }
+            };
+            let v = value.required_attribute("value")?;
+            set_attribute(&mut attributes, title.to_string(), v.to_string());
+        }
+        for id in &self.order {
+            if let (title, Some(default)) = &self.declarations[id] {
+                if !attributes.iter().any(|(name, _)| name == title) {
+                    attributes.push((title.to_string(), default.clone()));
+                }
+            }
+        }
+        Ok(attributes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    fn attributes(pairs: &[(&str, &str)]) -> Attributes {
+        pairs
+            .iter()
+            .map(|&(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_gexf_round_trip() {
+        let mut graph = DirectedGraph::<(String, String), String>::new();
+        let a = graph.add_node(("A".to_string(), "x\"y".to_string()));
+        let b = graph.add_node(("B".to_string(), String::new()));
+        graph.add_edge_with(&a, &b, "depends on".to_string());
+        graph.add_edge_with(&a, &a, "self".to_string());
+        graph.add_edge_with(&b, &a, String::new());
+
+        let output = graph.to_gexf(
+            |(label, note)| {
+                let mut list = attributes(&[("label", label)]);
+                if !note.is_empty() {
+                    list.push(("note".to_string(), note.clone()));
+                }
+                list
+            },
+            |label| {
+                if label.is_empty() {
+                    Attributes::new()
+                } else {
+                    attributes(&[("label", label)])
+                }
+            },
+        );
+        assert!(output.contains("<attribute id=\"0\" title=\"note\" type=\"string\"/>"));
+        assert!(output.contains("<attvalue for=\"0\" value=\"x&quot;y\"/>"));
+        assert!(output.contains("<node id=\"n1\" label=\"B\"/>"));
+        assert!(output.contains("<edge id=\"e1\" source=\"n0\" target=\"n0\" label=\"self\"/>"));
+
+        let attribute = |attributes: &Attributes, name: &str| {
+            attributes
+                .iter()
+                .find(|(n, _)| n == name)
+                .map_or(String::new(), |(_, value)| value.clone())
+        };
+        let parsed = DirectedGraph::from_gexf_with_edges(
+            &output,
+            |_, attributes| {
+                (
+                    attribute(attributes, "label"),
+                    attribute(attributes, "note"),
+                )
+            },
+            |attributes| attribute(attributes, "label"),
+        )
+        .unwrap();
+        assert_eq!(parsed.to_serialized(), graph.to_serialized());
+    }
+
+    #[test]
+    fn test_from_gexf() {
+        let input = r#"<?xml version="1.0" encoding="UTF-8"?>
+<gexf xmlns="http://www.gexf.net/1.2draft" version="1.2">
+  <graph mode="static" defaultedgetype="directed">
+    <attributes class="node">
+      <attribute id="0" title="kind" type="string"><default>lib</default></attribute>
+      <attribute id="1" title="size" type="integer"/>
+    </attributes>
+    <nodes>
+      <node id="a" label="Alpha"><attvalues><attvalue for="1" value="3"/></attvalues></node>
+      <node id="b"><attvalues><attvalue for="0" value="bin"/></attvalues></node>
+    </nodes>
+    <edges>
+      <edge id="0" source="a" target="b" weight="2.5"/>
+    </edges>
+  </graph>
+</gexf>"#;
+        let graph = DirectedGraph::from_gexf_with_edges(
+            input,
+            |id, attributes| format!("{}{:?}", id, attributes),
+            |attributes| attributes.clone(),
+        )
+        .unwrap();
+        let nodes: Vec<_> = graph
+            .nodes()
+            .iter()
+            .map(|n| n.ptr.borrow().data.clone())
+            .collect();
+        assert_eq!(
+            nodes,
+            vec![
+                r#"a[("label", "Alpha"), ("size", "3"), ("kind", "lib")]"#,
+                r#"b[("kind", "bin")]"#
+            ]
+        );
+        let a = graph.nodes()[0].ptr.borrow();
+        assert_eq!(
+            a.outgoing_edges().next().unwrap().1,
+            &attributes(&[("weight", "2.5")])
+        );
+
+        let error = |graph: &str| {
+            let input = format!("<gexf>\n{}\n</gexf>", graph);
+            let error = DirectedGraph::from_gexf(&input, |id, _| id.to_string())
+                .err()
+                .unwrap();
+            error.to_string()
+        };
+        assert_eq!(
+            error(
+                "<graph defaultedgetype=\"undirected\"><nodes><node id=\"a\"/></nodes>\n\
+                 <edges><edge source=\"a\" target=\"a\"/></edges></graph>"
+            ),
+            "line 3, column 8: undirected edges cannot be represented"
+        );
+        assert_eq!(
+            error(
+                "<graph><nodes><node id=\"a\"/></nodes>\n\
+                 <edges><edge source=\"a\" target=\"a\" type=\"mutual\"/></edges></graph>"
+            ),
+            "line 3, column 8: undirected edges cannot be represented"
+        );
+        assert_eq!(
+            error("<graph><nodes><node id=\"a\"><nodes/></node></nodes></graph>"),
+            "line 2, column 28: nested nodes are not supported"
+        );
+        assert_eq!(
+            error(
+                "<graph><nodes><node id=\"a\">\n\
+                 <attvalues><attvalue for=\"0\" value=\"1\"/></attvalues>\n\
+                 </node></nodes></graph>"
+            ),
+            "line 3, column 12: unknown node attribute '0'"
+        );
+    }
+}
@@ -0,0 +1,378 @@
+// GraphML output and input for DirectedGraph, e.g. for yEd.
+//
+// Node and edge data are mapped to and from GraphML attributes by closures, using the
+// same Attributes lists as the DOT format. Every attribute is declared as a string
+// key. Nodes are written with the IDs n0, n1, ... by their position in
+// DirectedGraph::nodes, and edges in the order of every node's `outgoing` list, so
+// reading a written graph back gives the same nodes and `outgoing` lists.
+
+use crate::dot::set_attribute;
+use crate::xml::{self, attribute_names, escape, Element, XmlError};
+use crate::{Attributes, DirectedGraph};
+use std::collections::HashMap;
+use std::fmt::Write;
+
+impl<T, E> DirectedGraph<T, E> {
+    // Write the graph as a GraphML document, with the attributes returned by
+    // `node_attributes` and `edge_attributes` for the data of every node and edge.
+    pub fn to_graphml<F, G>(&self, node_attributes: F, edge_attributes: G) -> String
+    where
+        F: Fn(&T) -> Attributes,
+        G: Fn(&E) -> Attributes,
+    {
+        let indices = self.node_indices();
+        let nodes: Vec<Attributes> = self
+            .nodes
+            .iter()
+            .map(|node| node_attributes(&node.ptr.borrow().data))
+            .collect();
+        let mut edges = Vec::new();
+        let mut edge_data = Vec::new();
+        for (i, node) in self.nodes.iter().enumerate() {
+            for (m, e) in node.ptr.borrow().outgoing_edges() {
+                edges.push((i, indices[m]));
+                edge_data.push(edge_attributes(e));
+            }
+        }
+        // Key IDs d0, d1, ... for the node attributes first, then for the edge ones.
+        let node_keys = attribute_names(&nodes);
+        let edge_keys = attribute_names(&edge_data);
+        let key_ids: HashMap<(&str, &str), String> = node_keys
+            .iter()
+            .map(|&name| ("node", name))
+            .chain(edge_keys.iter().map(|&name| ("edge", name)))
+            .enumerate()
+            .map(|(k, key)| (key, format!("d{}", k)))
+            .collect();
+
+        let mut out = String::new();
+        writeln!(out, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>").unwrap();
+        writeln!(
+            out,
+            "<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">"
+        )
+        .unwrap();
+        for (domain, names) in [("node", &node_keys), ("edge", &edge_keys)] {
+            for &name in names {
+                writeln!(
+                    out,
+                    "  <key id=\"{}\" for=\"{}\" attr.name=\"{}\" attr.type=\"string\"/>",
+                    key_ids[&(domain, name)],
+                    domain,
+                    escape(name)
+                )
+                .unwrap();
+            }
+        }
+        writeln!(out, "  <graph id=\"G\" edgedefault=\"directed\">").unwrap();
+        let write_data = |out: &mut String, domain: &str, attributes: &Attributes| {
+            for (name, value) in attributes {
+                let key = &key_ids[&(domain, name.as_str())];
+                writeln!(out, "      <data key=\"{}\">{}</data>", key, escape(value)).unwrap();
+            }
+        };
+        for (i, attributes) in nodes.iter().enumerate() {
+            if attributes.is_empty() {
+                writeln!(out, "    <node id=\"n{}\"/>", i).unwrap();
+            } else {
+                writeln!(out, "    <node id=\"n{}\">", i).unwrap();
+                write_data(&mut out, "node", attributes);
+                writeln!(out, "    </node>").unwrap();
+            }
+        }
+        for (k, ((from, to), attributes)) in edges.iter().zip(&edge_data).enumerate() {
+            let tag = format!("edge id=\"e{}\" source=\"n{}\" target=\"n{}\"", k, from, to);
+            if attributes.is_empty() {
+                writeln!(out, "    <{}/>", tag).unwrap();
+            } else {
+                writeln!(out, "    <{}>", tag).unwrap();
+                write_data(&mut out, "edge", attributes);
+                writeln!(out, "    </edge>").unwrap();
+            }
+        }
+        writeln!(out, "  </graph>").unwrap();
+        writeln!(out, "</graphml>").unwrap();
+        out
+    }
+}
+
+impl<T> DirectedGraph<T> {
+    // Read a graph from a GraphML document. `node` is called once for every node, in
+    // document order, with its ID and its attributes: the values of its <data>
+    // elements by attribute name, followed by the defaults of the keys it has no value
+    // for.
+    pub fn from_graphml<F>(input: &str, node: F) -> Result<Self, XmlError>
+    where
+        F: FnMut(&str, &Attributes) -> T,
+    {
+        DirectedGraph::from_graphml_with_edges(input, node, |_| ())
+    }
+}
+
+impl<T, E> DirectedGraph<T, E> {
+    // Like from_graphml, also creating the data of every edge from its attributes.
+    // Edges are added in document order. Undirected edges, hyperedges and nested
+    // graphs cannot be represented and are rejected, as are documents with more than
+    // one graph.
+    pub fn from_graphml_with_edges<F, G>(
+        input: &str,
+        mut node: F,
+        mut edge: G,
+    ) -> Result<Self, XmlError>
+    where
+        F: FnMut(&str, &Attributes) -> T,
+        G: FnMut(&Attributes) -> E,
+    {
+        let root = xml::parse(input)?;
+        if root.name != "graphml" {
+            return Err(root.error(format!(
+                "expected a <graphml> document, found <{}>",
+                root.name
+            )));
+        }
+        let keys = Keys::new(&root)?;
+        let graph = single_graph(&root)?;
+        let directed = match graph.attribute("edgedefault") {
+            None | Some("directed") => true,
+            Some("undirected") => false,
+            Some(other) => {
+                return Err(graph.error(format!("invalid edgedefault '{}'", other)));
+            }
+        };
+
+        let mut result = DirectedGraph::new();
+        let mut nodes = HashMap::new();
+        for element in graph.elements("node") {
+            let id = element.required_attribute("id")?;
+            if let Some(nested) = element.elements("graph").next() {
+                return Err(nested.error("nested graphs are not supported".into()));
+            }
+            if nodes.contains_key(id) {
+                return Err(element.error(format!("duplicate node ID '{}'", id)));
+            }
+            let attributes = keys.attributes(element, "node")?;
+            nodes.insert(id, result.add_node(node(id, &attributes)));
+        }
+        if let Some(hyperedge) = graph.elements("hyperedge").next() {
+            return Err(hyperedge.error("hyperedges cannot be represented".into()));
+        }
+        for element in graph.elements("edge") {
+            let edge_directed = match element.attribute("directed") {
+                None => directed,
+                Some("true") => true,
+                Some("false") => false,
+                Some(other) => {
+                    return Err(element.error(format!("invalid directed value '{}'", other)));
+                }
+            };
+            if !edge_directed {
+                return Err(element.error("undirected edges cannot be represented".into()));
+            }
+            let endpoint = |name| {
+                let id = element.required_attribute(name)?;
+                nodes
+                    .get(id)
+                    .ok_or_else(|| element.error(format!("unknown node '{}'", id)))
+            };
+            let (from, to) = (endpoint("source")?, endpoint("target")?);
+            let attributes = keys.attributes(element, "edge")?;
+            result.add_edge_with(from, to, edge(&attributes));
+        }
+        Ok(result)
+    }
+}
+
+// The one <graph> element of a document.
+fn single_graph(root: &Element) -> Result<&Element, XmlError> {
+    let mut graphs = root.elements("graph");
+    let graph = graphs
+        .next()
+        .ok_or_else(|| root.error("the document contains no graph".into()))?;
+    match graphs.next() {
+        Some(other) => Err(other.error("documents with several graphs are not supported".into())),
+        None => Ok(graph),
+    }
+}
+
+// The <key> declarations of a document.
+struct Keys<'a> {
+    // By key ID: the domain (node, edge, all, ...), the attribute name and the default.
+    keys: HashMap<&'a str, (&'a str, &'a str, Option<String>)>,
+    // Key IDs in document order.
+    order: Vec<&'a str>,
+}
+
+impl<'a> Keys<'a> {
+    fn new(root: &'a Element) -> Result<Self, XmlError> {
+        let mut keys = HashMap::new();
+        let mut order = Vec::new();
+        for key in root.elements("key") {
+            let id = key.required_attribute("id")?;
+            let domain = key.attribute("for").unwrap_or("all");
+            let name = key.attribute("attr.name").unwrap_or(id);
+            let default = key.elements("default").next().map(|d| d.text());
+            if keys.insert(id, (domain, name, default)).is_some() {
+                return Err(key.error(format!("duplicate key ID '{}'", id)));
+            }
+            order.push(id);
+        }
+        Ok(Keys { keys, order })
+    }
+
+    // The attributes of a node or edge element, from its <data> children and the key
+    // defaults.
+    fn attributes(&self, element: &Element, domain: &str) -> Result<Attributes, XmlError> {
+        let applies = |key_domain: &str| key_domain == domain || key_domain == "all";
+        let mut attributes = Attributes::new();
+        for data in element.elements("data") {
+            let id = data.required_attribute("key")?;
+            match self.keys.get(id) {
+                Some((key_domain, name, _)) if applies(key_domain) => {
+                    set_attribute(&mut attributes, name.to_string(), data.text());
+                }
+                _ => return Err(data.error(format!("unknown {} key '{}'", domain, id))),
+            }
+        }
+        for id in &self.order {
+            if let (key_domain, name, Some(default)) = &self.keys[id] {
+                if applies(key_domain) && !attributes.iter().any(|(n, _)| n == name) {
+                    attributes.push((name.to_string(), default.clone()));
+                }
+            }
+        }
+        Ok(attributes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    fn attributes(pairs: &[(&str, &str)]) -> Attributes {
+        pairs
+            .iter()
+            .map(|&(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_graphml_round_trip() {
+        let mut graph = DirectedGraph::<(String, u32), String>::new();
+        let a = graph.add_node(("a & <b>".to_string(), 1));
+        let b = graph.add_node(("b".to_string(), 2));
+        let c = graph.add_node(("c".to_string(), 3));
+        graph.add_edge_with(&a, &b, "build".to_string());
+        graph.add_edge_with(&a, &c, "runtime".to_string());
+        graph.add_edge_with(&c, &b, String::new());
+
+        let output = graph.to_graphml(
+            |(name, size)| attributes(&[("name", name), ("size", &size.to_string())]),
+            |kind| {
+                if kind.is_empty() {
+                    Attributes::new()
+                } else {
+                    attributes(&[("kind", kind)])
+                }
+            },
+        );
+        assert!(output
+            .contains("<key id=\"d2\" for=\"edge\" attr.name=\"kind\" attr.type=\"string\"/>"));
+        assert!(output.contains("<data key=\"d0\">a &amp; &lt;b&gt;</data>"));
+        assert!(output.contains("<edge id=\"e2\" source=\"n2\" target=\"n1\"/>"));
+
+        let attribute = |attributes: &Attributes, name: &str| {
+            attributes
+                .iter()
+                .find(|(n, _)| n == name)
+                .map_or(String::new(), |(_, value)| value.clone())
+        };
+        let parsed = DirectedGraph::from_graphml_with_edges(
+            &output,
+            |_, attributes| {
+                let size = attribute(attributes, "size").parse().unwrap();
+                (attribute(attributes, "name"), size)
+            },
+            |attributes| attribute(attributes, "kind"),
+        )
+        .unwrap();
+        assert_eq!(parsed.to_serialized(), graph.to_serialized());
+    }
+
+    #[test]
+    fn test_from_graphml() {
+        let input = r#"<?xml version="1.0" encoding="UTF-8"?>
+<graphml xmlns="http://graphml.graphdrawing.org/xmlns"
+    xmlns:y="http://www.yworks.com/xml/graphml">
+  <key id="color" for="node" attr.name="color" attr.type="string">
+    <default>yellow</default>
+  </key>
+  <key id="w" for="edge" attr.name="weight" attr.type="double"/>
+  <key id="g" for="node" yfiles.type="nodegraphics"/>
+  <graph id="G" edgedefault="undirected">
+    <edge source="x" target="y" directed="true"><data key="w">1.5</data></edge>
+    <node id="x"><data key="color">green</data></node>
+    <node id="y">
+      <data key="g"><y:ShapeNode/></data>
+    </node>
+  </graph>
+</graphml>"#;
+        let graph = DirectedGraph::from_graphml_with_edges(
+            input,
+            |id, attributes| format!("{}{:?}", id, attributes),
+            |attributes| attributes.clone(),
+        )
+        .unwrap();
+        let nodes: Vec<_> = graph
+            .nodes()
+            .iter()
+            .map(|n| n.ptr.borrow().data.clone())
+            .collect();
+        assert_eq!(
+            nodes,
+            vec![
+                r#"x[("color", "green")]"#,
+                r#"y[("g", ""), ("color", "yellow")]"#
+            ]
+        );
+        let x = graph.nodes()[0].ptr.borrow();
+        assert_eq!(
+            x.outgoing_edges().next().unwrap().1,
+            &attributes(&[("weight", "1.5")])
+        );
+
+        let error = |body: &str| {
+            let input = format!(
+                "<graphml>\n<graph edgedefault=\"undirected\">\n{}\n</graph>\n</graphml>",
+                body
+            );
+            let error = DirectedGraph::from_graphml(&input, |id, _| id.to_string())
+                .err()
+                .unwrap();
+            error.to_string()
+        };
+        assert_eq!(
+            error("<node id=\"a\"/><edge source=\"a\" target=\"a\"/>"),
+            "line 3, column 15: undirected edges cannot be represented"
+        );
+        assert_eq!(
+            error("<node id=\"a\"/><hyperedge><endpoint node=\"a\"/></hyperedge>"),
+            "line 3, column 15: hyperedges cannot be represented"
+        );
+        assert_eq!(
+            error("<node id=\"a\"/><edge source=\"a\" target=\"b\" directed=\"true\"/>"),
+            "line 3, column 15: unknown node 'b'"
+        );
+        assert_eq!(
+            error("<node id=\"a\"><data key=\"k\">1</data></node>"),
+            "line 3, column 14: unknown node key 'k'"
+        );
+        assert_eq!(
+            error("<node id=\"a\"><graph/></node>"),
+            "line 3, column 14: nested graphs are not supported"
+        );
+        assert_eq!(
+            error("<node/>"),
+            "line 3, column 1: <node> is missing the 'id' attribute"
+        );
+    }
+}
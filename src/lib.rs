@@ -1,82 +1,311 @@
+// NodeRef<T, E> hashes and compares by pointer identity, so it is a sound key even though
+// the node behind it has interior mutability.
+#![allow(clippy::mutable_key_type)]
+
 use std::cell::RefCell;
-use std::collections::HashSet;
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BinaryHeap, HashMap, HashSet, VecDeque};
+use std::error::Error;
+use std::fmt;
 use std::hash::{Hash, Hasher};
 use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
+
+pub mod arena;
+pub mod async_executor;
+mod bitset;
+mod components;
+mod dag;
+mod diagram;
+mod dot;
+pub mod executor;
+mod gexf;
+mod graphml;
+mod orderings;
+mod paths;
+mod reachability;
+mod serialized;
+pub mod sync;
+mod transitive;
+pub mod visit;
+mod xml;
+pub use dag::{AddEdgeError, Dag};
+pub use diagram::Diagram;
+pub use dot::{Attributes, Dot, DotError};
+pub use orderings::AllTopologicalOrders;
+pub use paths::{CriticalPath, NodeTiming, ShortestPaths};
+pub use serialized::{SerializedEdge, SerializedGraph, SerializedGraphError};
+pub use transitive::{ChainIndex, ReachabilityMatrix};
+pub use xml::XmlError;
 
-pub struct Node<T> {
+// A node of a DirectedGraph<T, E>, holding data of type T. Its edges can carry data of
+// type E, which defaults to () for graphs whose edges are only endpoints.
+pub struct Node<T, E = ()> {
     pub data: T,
-    pub incoming: Vec<NodeRef<T>>,
-    pub outgoing: Vec<NodeRef<T>>,
+    // The edges of the node, for reading; change them through DirectedGraph, which
+    // keeps them in step with the edge data below.
+    pub incoming: Vec<NodeRef<T, E>>,
+    pub outgoing: Vec<NodeRef<T, E>>,
+    // The data of the edges in `incoming` and `outgoing`, index for index, read through
+    // incoming_edges() and outgoing_edges(). Both endpoints of an edge share its data,
+    // which also tells parallel edges apart.
+    pub(crate) incoming_data: Vec<Rc<E>>,
+    pub(crate) outgoing_data: Vec<Rc<E>>,
+    // The id of the DirectedGraph the node belongs to, or 0 once it has been removed.
+    graph: usize,
 }
-impl<T> Node<T> {
-    fn new(x: T) -> Self {
+impl<T, E> Node<T, E> {
+    fn new(x: T, graph: usize) -> Self {
         Self {
             data: x,
             incoming: Vec::new(),
             outgoing: Vec::new(),
+            incoming_data: Vec::new(),
+            outgoing_data: Vec::new(),
+            graph,
         }
     }
+
+    // The predecessors of this node with the data of the edge from each, in edge order.
+    pub fn incoming_edges(&self) -> impl Iterator<Item = (&NodeRef<T, E>, &E)> {
+        self.incoming
+            .iter()
+            .zip(self.incoming_data.iter().map(|data| &**data))
+    }
+
+    // The successors of this node with the data of the edge to each, in edge order.
+    pub fn outgoing_edges(&self) -> impl Iterator<Item = (&NodeRef<T, E>, &E)> {
+        self.outgoing
+            .iter()
+            .zip(self.outgoing_data.iter().map(|data| &**data))
+    }
 }
 
-pub struct NodeRef<T> {
-    pub ptr: Rc<RefCell<Node<T>>>,
+pub struct NodeRef<T, E = ()> {
+    pub ptr: Rc<RefCell<Node<T, E>>>,
 }
-impl<T> NodeRef<T> {
-    fn new(data: T) -> NodeRef<T> {
+impl<T, E> NodeRef<T, E> {
+    fn new(data: T, graph: usize) -> NodeRef<T, E> {
         NodeRef {
-            ptr: Rc::new(RefCell::new(Node::new(data))),
+            ptr: Rc::new(RefCell::new(Node::new(data, graph))),
         }
     }
 }
-impl<T> Clone for NodeRef<T> {
-    fn clone(&self) -> NodeRef<T> {
+impl<T, E> Clone for NodeRef<T, E> {
+    fn clone(&self) -> NodeRef<T, E> {
         NodeRef {
             ptr: self.ptr.clone(),
         }
     }
 }
 
-// Reference equality semantics for NodeRef<T>.
-impl<T> Eq for NodeRef<T> {}
-impl<T> PartialEq for NodeRef<T> {
-    fn eq(&self, rhs: &NodeRef<T>) -> bool {
+// Reference equality semantics for NodeRef<T, E>.
+impl<T, E> Eq for NodeRef<T, E> {}
+impl<T, E> PartialEq for NodeRef<T, E> {
+    fn eq(&self, rhs: &NodeRef<T, E>) -> bool {
         self.ptr.as_ptr().eq(&rhs.ptr.as_ptr())
     }
 }
-impl<T> Hash for NodeRef<T> {
+impl<T, E> Hash for NodeRef<T, E> {
     fn hash<H: Hasher>(&self, state: &mut H) {
         self.ptr.as_ptr().hash(state)
     }
 }
 
-fn remove_item<T>(vec: &mut Vec<T>, item: &T)
+impl<T, E> fmt::Debug for NodeRef<T, E>
 where
-    T: PartialEq,
+    T: fmt::Debug,
 {
-    vec.retain(|e| e != item)
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("NodeRef")
+            .field(&self.ptr.borrow().data)
+            .finish()
+    }
+}
+
+// The error returned when a topological sort is requested for a graph with a cycle.
+pub struct CycleError<T, E = ()> {
+    // The nodes of one concrete cycle, in edge order. The closing edge goes from the
+    // last node back to the first one.
+    pub cycle: Vec<NodeRef<T, E>>,
+    // All nodes that could not be ordered: the nodes on a cycle and the nodes
+    // reachable from one, in insertion order.
+    pub unordered: Vec<NodeRef<T, E>>,
+}
+
+impl<T, E> fmt::Debug for CycleError<T, E>
+where
+    T: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CycleError")
+            .field("cycle", &self.cycle)
+            .field("unordered", &self.unordered)
+            .finish()
+    }
+}
+
+// Formats the cycle as e.g. "cycle detected: A -> B -> C -> A".
+impl<T, E> fmt::Display for CycleError<T, E>
+where
+    T: fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "cycle detected: ")?;
+        for (i, node) in self.cycle.iter().chain(self.cycle.first()).enumerate() {
+            if i > 0 {
+                write!(f, " -> ")?;
+            }
+            write!(f, "{}", node.ptr.borrow().data)?;
+        }
+        Ok(())
+    }
+}
+
+impl<T, E> Error for CycleError<T, E> where T: fmt::Debug + fmt::Display {}
+
+// Remove every edge to or from `item` from a node's `incoming` or `outgoing` list,
+// along with the matching entries of its edge data list.
+fn remove_item<T, E>(vec: &mut Vec<NodeRef<T, E>>, data: &mut Vec<Rc<E>>, item: &NodeRef<T, E>) {
+    let mut keep = vec.iter().map(|e| e != item);
+    data.retain(|_| keep.next().unwrap());
+    vec.retain(|e| e != item);
 }
 
-#[derive(Default)]
-pub struct DirectedGraph<T> {
-    nodes: Vec<NodeRef<T>>,
+// Hands out the ids that tell which graph a node belongs to. 0 is never used.
+static NEXT_GRAPH_ID: AtomicUsize = AtomicUsize::new(1);
+
+pub struct DirectedGraph<T, E = ()> {
+    id: usize,
+    nodes: Vec<NodeRef<T, E>>,
+}
+// Implemented by hand because #[derive(Default)] would require T: Default. Only
+// implemented for graphs without edge data, so that DirectedGraph::default() needs no
+// type annotations; use new() for other edge types.
+impl<T> Default for DirectedGraph<T> {
+    fn default() -> Self {
+        DirectedGraph::new()
+    }
 }
-impl<T> DirectedGraph<T> {
-    pub fn add_node(&mut self, data: T) -> NodeRef<T> {
-        let result = NodeRef::new(data);
+impl<T, E> DirectedGraph<T, E> {
+    pub fn new() -> Self {
+        DirectedGraph {
+            id: NEXT_GRAPH_ID.fetch_add(1, AtomicOrdering::Relaxed),
+            nodes: Vec::new(),
+        }
+    }
+
+    // All nodes of the graph, in insertion order.
+    pub fn nodes(&self) -> &[NodeRef<T, E>] {
+        &self.nodes
+    }
+
+    // Whether `node` is a node of this graph: it was added to it and not removed since.
+    pub fn contains(&self, node: &NodeRef<T, E>) -> bool {
+        node.ptr.borrow().graph == self.id
+    }
+
+    pub fn add_node(&mut self, data: T) -> NodeRef<T, E> {
+        let result = NodeRef::new(data, self.id);
         self.nodes.push(result.clone());
         result
     }
 
-    pub fn add_edge(&mut self, from: &NodeRef<T>, to: &NodeRef<T>) {
-        from.ptr.borrow_mut().outgoing.push(to.clone());
-        to.ptr.borrow_mut().incoming.push(from.clone());
+    // Add an edge with default data, e.g. () for graphs without edge data.
+    pub fn add_edge(&mut self, from: &NodeRef<T, E>, to: &NodeRef<T, E>) -> bool
+    where
+        E: Default,
+    {
+        self.add_edge_with(from, to, E::default())
+    }
+
+    // Add an edge with the given data. Returns false without adding it if either end is
+    // not a node of this graph, as the graph's algorithms only know about its own nodes.
+    // The data is shared by both ends through an Rc, so every edge costs one heap
+    // allocation, even when E is ().
+    pub fn add_edge_with(&mut self, from: &NodeRef<T, E>, to: &NodeRef<T, E>, data: E) -> bool {
+        if !self.contains(from) || !self.contains(to) {
+            return false;
+        }
+        let data = Rc::new(data);
+        let mut from_node = from.ptr.borrow_mut();
+        from_node.outgoing.push(to.clone());
+        from_node.outgoing_data.push(data.clone());
+        drop(from_node);
+        let mut to_node = to.ptr.borrow_mut();
+        to_node.incoming.push(from.clone());
+        to_node.incoming_data.push(data);
+        true
+    }
+
+    // Remove a node and all edges to and from it. Returns false if the node is not part
+    // of this graph. Other NodeRefs to the node stay valid, but it no longer has edges.
+    pub fn remove_node(&mut self, node: &NodeRef<T, E>) -> bool {
+        match self.nodes.iter().position(|n| n == node) {
+            None => false,
+            Some(i) => {
+                self.clear_edges_of(node);
+                self.nodes.remove(i);
+                node.ptr.borrow_mut().graph = 0;
+                true
+            }
+        }
+    }
+
+    // Remove one edge from `from` to `to`. If there are parallel edges, only one of them
+    // is removed, so the edge count between the two nodes goes down by exactly one.
+    // Returns false if there is no such edge.
+    pub fn remove_edge(&mut self, from: &NodeRef<T, E>, to: &NodeRef<T, E>) -> bool {
+        let mut from_node = from.ptr.borrow_mut();
+        match from_node.outgoing.iter().position(|n| n == to) {
+            None => false,
+            Some(i) => {
+                from_node.outgoing.remove(i);
+                let data = from_node.outgoing_data.remove(i);
+                drop(from_node);
+                // Find the other end of this particular edge, by its data.
+                let mut to_node = to.ptr.borrow_mut();
+                let j = (0..to_node.incoming.len())
+                    .find(|&j| {
+                        to_node.incoming[j] == *from && Rc::ptr_eq(&to_node.incoming_data[j], &data)
+                    })
+                    .unwrap();
+                to_node.incoming.remove(j);
+                to_node.incoming_data.remove(j);
+                true
+            }
+        }
+    }
+
+    // Remove all edges to and from a node, including parallel edges and self loops.
+    pub fn clear_edges_of(&mut self, node: &NodeRef<T, E>) {
+        let (incoming, outgoing) = {
+            let mut n = node.ptr.borrow_mut();
+            n.incoming_data.clear();
+            n.outgoing_data.clear();
+            (
+                std::mem::take(&mut n.incoming),
+                std::mem::take(&mut n.outgoing),
+            )
+        };
+        for m in &outgoing {
+            let m = &mut *m.ptr.borrow_mut();
+            remove_item(&mut m.incoming, &mut m.incoming_data, node);
+        }
+        for m in &incoming {
+            let m = &mut *m.ptr.borrow_mut();
+            remove_item(&mut m.outgoing, &mut m.outgoing_data, node);
+        }
     }
 
     // Try to compute a topological sort using Kahn's algorithm.
     // This consumes the graph, because Kahn's algorithm involves removing incoming edges
-    // as you go, but with a bit more effort we could write a version that preserves the graph.
-    // If a topological sort exists, one is returned, otherwise None is returned.
-    pub fn topological_sort(self) -> Option<Vec<NodeRef<T>>> {
+    // as you go; use topological_order to sort without consuming the graph.
+    // If a topological sort exists, one is returned, otherwise an error describing a cycle.
+    // The returned nodes keep their outgoing edges but have no incoming edges. On error,
+    // the graph is dropped, which removes all edges, so the nodes in the CycleError have
+    // none.
+    pub fn topological_sort(mut self) -> Result<Vec<NodeRef<T, E>>, CycleError<T, E>> {
         // result will contain the sorted elements
         let mut result = Vec::new();
         // S is a set of all nodes with no incoming edges
@@ -84,7 +313,7 @@ impl<T> DirectedGraph<T> {
             .nodes
             .iter()
             .filter(|n| n.ptr.borrow().incoming.is_empty())
-            .map(|e| e.clone())
+            .cloned()
             .collect();
         while !s.is_empty() {
             // remove a node n from S
@@ -99,9 +328,10 @@ impl<T> DirectedGraph<T> {
                 // remove the edge e from the graph
                 // (we only bother removing the incoming edge since that's all we need
                 // and we are consuming the graph anyways)
-                remove_item(&mut m.ptr.borrow_mut().incoming, &n);
+                let m_node = &mut *m.ptr.borrow_mut();
+                remove_item(&mut m_node.incoming, &mut m_node.incoming_data, &n);
                 // if m has no other incoming edges
-                if m.ptr.borrow().incoming.is_empty() {
+                if m_node.incoming.is_empty() {
                     // insert m into S
                     s.insert(m.clone());
                 }
@@ -110,11 +340,230 @@ impl<T> DirectedGraph<T> {
         // if the graph has remaining (incoming) edges, there is at least one cycle
         for node in &self.nodes {
             if !node.ptr.borrow().incoming.is_empty() {
-                return None;
+                return Err(self.cycle_error(&result));
+            }
+        }
+        // otherwise we have a topological sort. The outgoing edges that are left all
+        // point forward in it, so they form no Rc cycles and Drop need not remove them.
+        self.nodes.clear();
+        Ok(result)
+    }
+
+    // Compute a topological sort using Kahn's algorithm without modifying the graph.
+    // Instead of removing incoming edges as we go, we keep a counter of the not yet
+    // processed incoming edges of every node, so the graph can be sorted repeatedly.
+    // If a topological sort exists, one is returned, otherwise an error describing a cycle.
+    pub fn topological_order(&self) -> Result<Vec<NodeRef<T, E>>, CycleError<T, E>> {
+        let indices = self.node_indices();
+        // in_degree[i] is the number of unprocessed incoming edges of nodes[i]
+        let mut in_degree: Vec<usize> = self
+            .nodes
+            .iter()
+            .map(|n| n.ptr.borrow().incoming.len())
+            .collect();
+        // S is the queue of all nodes with no unprocessed incoming edges
+        let mut s: VecDeque<usize> = (0..self.nodes.len())
+            .filter(|&i| in_degree[i] == 0)
+            .collect();
+        let mut result = Vec::with_capacity(self.nodes.len());
+        while let Some(i) = s.pop_front() {
+            let n = &self.nodes[i];
+            result.push(n.clone());
+            for m in &n.ptr.borrow().outgoing {
+                // "remove" the edge from n to m by decrementing m's counter
+                let j = indices[m];
+                in_degree[j] -= 1;
+                if in_degree[j] == 0 {
+                    s.push_back(j);
+                }
+            }
+        }
+        // if some node was never reached, there is at least one cycle
+        if result.len() == self.nodes.len() {
+            Ok(result)
+        } else {
+            Err(self.cycle_error(&result))
+        }
+    }
+
+    // Like topological_order, but deterministic: whenever several nodes are ready,
+    // the one that was added to the graph first comes first. The result is the
+    // lexicographically smallest topological sort with respect to insertion order.
+    pub fn stable_topological_order(&self) -> Result<Vec<NodeRef<T, E>>, CycleError<T, E>> {
+        let rank: Vec<usize> = (0..self.nodes.len()).collect();
+        self.topological_order_by_rank(&rank)
+    }
+
+    // Like stable_topological_order, but whenever several nodes are ready, the smallest
+    // one according to `compare` comes first, so the result is the lexicographically
+    // smallest topological sort under `compare`. Nodes that compare equal are taken
+    // in insertion order.
+    pub fn topological_order_by<F>(
+        &self,
+        mut compare: F,
+    ) -> Result<Vec<NodeRef<T, E>>, CycleError<T, E>>
+    where
+        F: FnMut(&T, &T) -> Ordering,
+    {
+        let mut by_priority: Vec<usize> = (0..self.nodes.len()).collect();
+        by_priority.sort_by(|&i, &j| {
+            compare(
+                &self.nodes[i].ptr.borrow().data,
+                &self.nodes[j].ptr.borrow().data,
+            )
+        });
+        let mut rank = vec![0; self.nodes.len()];
+        for (r, &i) in by_priority.iter().enumerate() {
+            rank[i] = r;
+        }
+        self.topological_order_by_rank(&rank)
+    }
+
+    // Like topological_order_by, comparing nodes by the key extracted by `f`.
+    pub fn topological_order_by_key<K, F>(
+        &self,
+        mut f: F,
+    ) -> Result<Vec<NodeRef<T, E>>, CycleError<T, E>>
+    where
+        K: Ord,
+        F: FnMut(&T) -> K,
+    {
+        self.topological_order_by(|a, b| f(a).cmp(&f(b)))
+    }
+
+    // Split the nodes into layers (a.k.a. generations): the first layer contains the nodes
+    // without incoming edges, and every other layer contains the nodes whose predecessors
+    // all live in earlier layers, with at least one in the layer right before. Nodes in
+    // the same layer do not depend on each other and appear in insertion order.
+    #[allow(clippy::type_complexity)]
+    pub fn topological_layers(&self) -> Result<Vec<Vec<NodeRef<T, E>>>, CycleError<T, E>> {
+        let indices = self.node_indices();
+        let mut in_degree: Vec<usize> = self
+            .nodes
+            .iter()
+            .map(|n| n.ptr.borrow().incoming.len())
+            .collect();
+        let mut layer: Vec<usize> = (0..self.nodes.len())
+            .filter(|&i| in_degree[i] == 0)
+            .collect();
+        let mut result = Vec::new();
+        let mut count = 0;
+        while !layer.is_empty() {
+            let mut next = Vec::new();
+            for &i in &layer {
+                for m in &self.nodes[i].ptr.borrow().outgoing {
+                    let j = indices[m];
+                    in_degree[j] -= 1;
+                    if in_degree[j] == 0 {
+                        next.push(j);
+                    }
+                }
             }
+            next.sort_unstable();
+            count += layer.len();
+            result.push(layer.iter().map(|&i| self.nodes[i].clone()).collect());
+            layer = next;
+        }
+        if count == self.nodes.len() {
+            Ok(result)
+        } else {
+            let ordered: Vec<_> = result.into_iter().flatten().collect();
+            Err(self.cycle_error(&ordered))
+        }
+    }
+
+    // Kahn's algorithm where the ready node with the smallest rank[i] is always taken next.
+    fn topological_order_by_rank(
+        &self,
+        rank: &[usize],
+    ) -> Result<Vec<NodeRef<T, E>>, CycleError<T, E>> {
+        let indices = self.node_indices();
+        let mut in_degree: Vec<usize> = self
+            .nodes
+            .iter()
+            .map(|n| n.ptr.borrow().incoming.len())
+            .collect();
+        // S is a min-heap of (rank, index) of all nodes with no unprocessed incoming edges
+        let mut s: BinaryHeap<Reverse<(usize, usize)>> = (0..self.nodes.len())
+            .filter(|&i| in_degree[i] == 0)
+            .map(|i| Reverse((rank[i], i)))
+            .collect();
+        let mut result = Vec::with_capacity(self.nodes.len());
+        while let Some(Reverse((_, i))) = s.pop() {
+            let n = &self.nodes[i];
+            result.push(n.clone());
+            for m in &n.ptr.borrow().outgoing {
+                let j = indices[m];
+                in_degree[j] -= 1;
+                if in_degree[j] == 0 {
+                    s.push(Reverse((rank[j], j)));
+                }
+            }
+        }
+        if result.len() == self.nodes.len() {
+            Ok(result)
+        } else {
+            Err(self.cycle_error(&result))
+        }
+    }
+
+    // Build the error for a failed topological sort, given the nodes that could be ordered.
+    // Every node that could not be ordered has a predecessor that could not be ordered
+    // either, so walking backwards along those predecessors must eventually repeat a node.
+    fn cycle_error(&self, ordered: &[NodeRef<T, E>]) -> CycleError<T, E> {
+        let ordered: HashSet<_> = ordered.iter().collect();
+        let unordered: Vec<_> = self
+            .nodes
+            .iter()
+            .filter(|n| !ordered.contains(n))
+            .cloned()
+            .collect();
+        let mut path = Vec::new();
+        let mut position = HashMap::new();
+        let mut n = unordered[0].clone();
+        while !position.contains_key(&n) {
+            position.insert(n.clone(), path.len());
+            path.push(n.clone());
+            let pred = n
+                .ptr
+                .borrow()
+                .incoming
+                .iter()
+                .find(|m| !ordered.contains(m))
+                .unwrap()
+                .clone();
+            n = pred;
+        }
+        // path[position[n]..] is the cycle, walked against the edge direction
+        let mut cycle = path.split_off(position[&n]);
+        cycle.reverse();
+        cycle.rotate_right(1);
+        CycleError { cycle, unordered }
+    }
+
+    // Map every node of the graph to its position in `nodes`.
+    fn node_indices(&self) -> HashMap<NodeRef<T, E>, usize> {
+        self.nodes
+            .iter()
+            .enumerate()
+            .map(|(i, n)| (n.clone(), i))
+            .collect()
+    }
+}
+
+// Edges hold strong references in both directions, so any graph with at least one edge
+// contains Rc cycles. Tear all edges down when the graph goes away so that every node
+// not referenced from outside the graph is freed. NodeRefs that outlive the graph keep
+// their node alive, but without any edges.
+impl<T, E> Drop for DirectedGraph<T, E> {
+    fn drop(&mut self) {
+        for node in &self.nodes {
+            let mut n = node.ptr.borrow_mut();
+            n.incoming.clear();
+            n.outgoing.clear();
+            n.incoming_data.clear();
+            n.outgoing_data.clear();
         }
-        // otherwise we have a topological sort
-        Some(result)
     }
 }
 
@@ -142,8 +591,8 @@ mod tests {
 
         // Compute a topological sort and check that it's correct.
         assert!(match graph.topological_sort() {
-            None => false,
-            Some(nodes) => {
+            Err(_) => false,
+            Ok(nodes) => {
                 let str = nodes.iter().fold(String::new(), |acc, node| {
                     format!("{}{}", acc, node.ptr.borrow().data)
                 });
@@ -152,4 +601,295 @@ mod tests {
             }
         })
     }
+
+    #[test]
+    fn test_topological_order_preserves_graph() {
+        let mut graph = DirectedGraph::default();
+        let a = graph.add_node('A');
+        let b = graph.add_node('B');
+        let c = graph.add_node('C');
+        graph.add_edge(&a, &b);
+        graph.add_edge(&b, &c);
+        graph.add_edge(&a, &c);
+
+        let order = |graph: &DirectedGraph<char>| {
+            graph.topological_order().ok().map(|nodes| {
+                nodes
+                    .iter()
+                    .map(|node| node.ptr.borrow().data)
+                    .collect::<String>()
+            })
+        };
+        assert_eq!(order(&graph), Some("ABC".to_string()));
+        // The edges are still there, so sorting again gives the same answer...
+        assert_eq!(c.ptr.borrow().incoming.len(), 2);
+        assert_eq!(order(&graph), Some("ABC".to_string()));
+        // ...and the graph can keep being edited.
+        graph.add_edge(&c, &a);
+        assert_eq!(order(&graph), None);
+    }
+
+    #[test]
+    fn test_stable_topological_order() {
+        // The same diamond as in test_topological_sort, plus an isolated node E.
+        let mut graph = DirectedGraph::default();
+        graph.add_node('E');
+        let a = graph.add_node('A');
+        let c = graph.add_node('C');
+        let b = graph.add_node('B');
+        let d = graph.add_node('D');
+        graph.add_edge(&a, &b);
+        graph.add_edge(&a, &c);
+        graph.add_edge(&b, &d);
+        graph.add_edge(&c, &d);
+
+        let to_string = |nodes: Result<Vec<NodeRef<char>>, CycleError<char>>| {
+            nodes.ok().map(|nodes| {
+                nodes
+                    .iter()
+                    .map(|node| node.ptr.borrow().data)
+                    .collect::<String>()
+            })
+        };
+        // Ties are broken by insertion order...
+        assert_eq!(
+            to_string(graph.stable_topological_order()),
+            Some("EACBD".to_string())
+        );
+        // ...or by a user supplied priority.
+        assert_eq!(
+            to_string(graph.topological_order_by_key(|&x| x)),
+            Some("ABCDE".to_string())
+        );
+        assert_eq!(
+            to_string(graph.topological_order_by(|x, y| y.cmp(x))),
+            Some("EACBD".to_string())
+        );
+    }
+
+    #[test]
+    fn test_topological_layers() {
+        //   A   B
+        //  / \ /
+        // C   D
+        //  \ / \
+        //   E   |
+        //    \  /
+        //     F
+        let mut graph = DirectedGraph::default();
+        let f = graph.add_node('F');
+        let e = graph.add_node('E');
+        let d = graph.add_node('D');
+        let c = graph.add_node('C');
+        let b = graph.add_node('B');
+        let a = graph.add_node('A');
+        graph.add_edge(&a, &c);
+        graph.add_edge(&a, &d);
+        graph.add_edge(&b, &d);
+        graph.add_edge(&c, &e);
+        graph.add_edge(&d, &e);
+        graph.add_edge(&d, &f);
+        graph.add_edge(&e, &f);
+
+        let layers: Vec<String> = graph
+            .topological_layers()
+            .ok()
+            .unwrap()
+            .iter()
+            .map(|layer| layer.iter().map(|node| node.ptr.borrow().data).collect())
+            .collect();
+        assert_eq!(layers, vec!["BA", "DC", "E", "F"]);
+
+        graph.add_edge(&f, &d);
+        let err = graph.topological_layers().err().unwrap();
+        assert_eq!(err.to_string(), "cycle detected: F -> D -> F");
+    }
+
+    #[test]
+    fn test_remove() {
+        let mut graph = DirectedGraph::default();
+        let a = graph.add_node('A');
+        let b = graph.add_node('B');
+        let c = graph.add_node('C');
+        graph.add_edge(&a, &b);
+        graph.add_edge(&a, &b);
+        graph.add_edge(&b, &c);
+        graph.add_edge(&c, &a);
+        graph.add_edge(&b, &b);
+
+        // Parallel edges are removed one at a time.
+        assert!(graph.remove_edge(&a, &b));
+        assert_eq!(a.ptr.borrow().outgoing, vec![b.clone()]);
+        assert_eq!(b.ptr.borrow().incoming, vec![a.clone(), b.clone()]);
+        assert!(graph.remove_edge(&a, &b));
+        assert!(!graph.remove_edge(&a, &b));
+        assert!(graph.remove_edge(&b, &b));
+        assert!(b.ptr.borrow().incoming.is_empty());
+
+        graph.add_edge(&a, &b);
+        graph.clear_edges_of(&a);
+        assert!(a.ptr.borrow().incoming.is_empty());
+        assert!(a.ptr.borrow().outgoing.is_empty());
+        assert!(b.ptr.borrow().incoming.is_empty());
+        assert!(c.ptr.borrow().outgoing.is_empty());
+
+        graph.add_edge(&a, &b);
+        assert!(graph.remove_node(&b));
+        assert!(!graph.remove_node(&b));
+        assert!(a.ptr.borrow().outgoing.is_empty());
+        assert!(c.ptr.borrow().incoming.is_empty());
+        let order: String = graph
+            .stable_topological_order()
+            .ok()
+            .unwrap()
+            .iter()
+            .map(|node| node.ptr.borrow().data)
+            .collect();
+        assert_eq!(order, "AC");
+
+        // Edges to removed nodes and to nodes of other graphs are rejected, so the
+        // algorithms never meet a node they do not know.
+        let mut other = DirectedGraph::default();
+        let x = other.add_node('X');
+        assert!(!graph.contains(&b));
+        assert!(!graph.add_edge(&a, &b));
+        assert!(!graph.add_edge(&a, &x));
+        assert!(!graph.add_edge(&x, &c));
+        assert!(graph.add_edge(&a, &c));
+        assert!(a.ptr.borrow().outgoing == vec![c.clone()] && x.ptr.borrow().incoming.is_empty());
+        assert_eq!(graph.topological_order().ok().unwrap(), vec![a.clone(), c]);
+        assert_eq!(graph.topological_layers().ok().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_edge_data() {
+        let mut graph = DirectedGraph::<char, &str>::new();
+        let a = graph.add_node('A');
+        let b = graph.add_node('B');
+        let c = graph.add_node('C');
+        graph.add_edge_with(&a, &b, "first");
+        graph.add_edge_with(&a, &b, "second");
+        graph.add_edge_with(&c, &b, "third");
+        graph.add_edge(&b, &c);
+
+        let labels = |edges: Vec<(&NodeRef<char, &str>, &&str)>| -> Vec<String> {
+            edges
+                .into_iter()
+                .map(|(n, e)| format!("{}:{}", n.ptr.borrow().data, e))
+                .collect()
+        };
+        assert_eq!(
+            labels(b.ptr.borrow().incoming_edges().collect()),
+            vec!["A:first", "A:second", "C:third"]
+        );
+        assert_eq!(
+            labels(a.ptr.borrow().outgoing_edges().collect()),
+            vec!["B:first", "B:second"]
+        );
+        assert_eq!(
+            labels(b.ptr.borrow().outgoing_edges().collect()),
+            vec!["C:"]
+        );
+
+        // Removal keeps the remaining edges' data aligned at both ends.
+        assert!(graph.remove_edge(&a, &b));
+        assert_eq!(
+            labels(b.ptr.borrow().incoming_edges().collect()),
+            vec!["A:second", "C:third"]
+        );
+        graph.clear_edges_of(&a);
+        assert_eq!(
+            labels(b.ptr.borrow().incoming_edges().collect()),
+            vec!["C:third"]
+        );
+    }
+
+    #[test]
+    fn test_drop_frees_nodes() {
+        use std::cell::Cell;
+
+        struct DropCounter(Rc<Cell<usize>>);
+        impl Drop for DropCounter {
+            fn drop(&mut self) {
+                self.0.set(self.0.get() + 1);
+            }
+        }
+
+        let drops = Rc::new(Cell::new(0));
+        let mut graph = DirectedGraph::default();
+        let nodes: Vec<_> = (0..4)
+            .map(|_| graph.add_node(DropCounter(drops.clone())))
+            .collect();
+        // A diamond, a cycle, a self loop and parallel edges.
+        graph.add_edge(&nodes[0], &nodes[1]);
+        graph.add_edge(&nodes[0], &nodes[2]);
+        graph.add_edge(&nodes[1], &nodes[3]);
+        graph.add_edge(&nodes[2], &nodes[3]);
+        graph.add_edge(&nodes[3], &nodes[0]);
+        graph.add_edge(&nodes[2], &nodes[2]);
+        graph.add_edge(&nodes[1], &nodes[3]);
+        let kept = nodes[1].clone();
+        drop(nodes);
+
+        drop(graph);
+        assert_eq!(drops.get(), 3);
+        assert!(kept.ptr.borrow().outgoing.is_empty());
+        drop(kept);
+        assert_eq!(drops.get(), 4);
+
+        // Consuming the graph with a sort must not leak either. A successful sort keeps
+        // the outgoing edges, which are freed along with the nodes.
+        let drops = Rc::new(Cell::new(0));
+        let mut graph = DirectedGraph::default();
+        let a = graph.add_node(DropCounter(drops.clone()));
+        let b = graph.add_node(DropCounter(drops.clone()));
+        graph.add_edge(&a, &b);
+        drop((a, b));
+        let sorted = graph.topological_sort().ok().unwrap();
+        assert!(sorted[0].ptr.borrow().outgoing == vec![sorted[1].clone()]);
+        assert!(sorted[1].ptr.borrow().incoming.is_empty());
+        drop(sorted);
+        assert_eq!(drops.get(), 2);
+
+        // A failed sort drops the graph, so the nodes of the cycle lose their edges.
+        let drops = Rc::new(Cell::new(0));
+        let mut graph = DirectedGraph::default();
+        let a = graph.add_node(DropCounter(drops.clone()));
+        let b = graph.add_node(DropCounter(drops.clone()));
+        graph.add_edge(&a, &b);
+        graph.add_edge(&b, &a);
+        drop((a, b));
+        let err = graph.topological_sort().err().unwrap();
+        assert!(err.cycle[0].ptr.borrow().outgoing.is_empty());
+        drop(err);
+        assert_eq!(drops.get(), 2);
+    }
+
+    #[test]
+    fn test_cycle_error() {
+        // A -> B -> C -> A is a cycle, D feeds into it and E hangs off it.
+        let mut graph = DirectedGraph::default();
+        let a = graph.add_node('A');
+        let b = graph.add_node('B');
+        let c = graph.add_node('C');
+        let d = graph.add_node('D');
+        let e = graph.add_node('E');
+        graph.add_edge(&a, &b);
+        graph.add_edge(&b, &c);
+        graph.add_edge(&c, &a);
+        graph.add_edge(&d, &a);
+        graph.add_edge(&c, &e);
+
+        let err = graph.topological_order().err().unwrap();
+        assert_eq!(err.cycle, vec![a.clone(), b.clone(), c.clone()]);
+        assert_eq!(
+            err.unordered,
+            vec![a.clone(), b.clone(), c.clone(), e.clone()]
+        );
+        assert_eq!(err.to_string(), "cycle detected: A -> B -> C -> A");
+
+        let err = graph.topological_sort().err().unwrap();
+        assert_eq!(err.cycle, vec![a, b, c]);
+        assert_eq!(err.to_string(), "cycle detected: A -> B -> C -> A");
+    }
 }
@@ -0,0 +1,223 @@
+use crate::{DirectedGraph, NodeRef};
+use std::collections::HashMap;
+
+impl<T, E> DirectedGraph<T, E> {
+    // Iterate over every topological sort of the graph, generated lazily by
+    // backtracking. The sorts come in lexicographic order with respect to insertion
+    // order, so the first one is stable_topological_order(). A graph with a cycle has
+    // no topological sorts. The iterator works on a snapshot of the edges taken when it
+    // is created.
+    pub fn all_topological_orders(&self) -> AllTopologicalOrders<T, E> {
+        let indices = self.node_indices();
+        let n = self.nodes.len();
+        AllTopologicalOrders {
+            nodes: self.nodes.clone(),
+            successors: self
+                .nodes
+                .iter()
+                .map(|node| {
+                    node.ptr
+                        .borrow()
+                        .outgoing
+                        .iter()
+                        .map(|m| indices[m])
+                        .collect()
+                })
+                .collect(),
+            in_degree: self
+                .nodes
+                .iter()
+                .map(|node| node.ptr.borrow().incoming.len())
+                .collect(),
+            used: vec![false; n],
+            chosen: Vec::with_capacity(n),
+            started: false,
+        }
+    }
+
+    // Count the topological sorts of the graph (its linear extensions) without
+    // enumerating them, by dynamic programming over the sets of nodes that can form a
+    // prefix of a sort. That number of sets can be exponential in the width of the
+    // graph, so the computation gives up and returns None once it has seen more than
+    // `max_states` of them. None is also returned for graphs with more than 64 nodes,
+    // or if the count does not fit in a u128. A graph with a cycle has 0 sorts.
+    pub fn count_topological_orders(&self, max_states: usize) -> Option<u128> {
+        let n = self.nodes.len();
+        if n > 64 {
+            return None;
+        }
+        let indices = self.node_indices();
+        // predecessors[i] is the set of predecessors of node i, as a bit mask
+        let predecessors: Vec<u64> = self
+            .nodes
+            .iter()
+            .map(|node| {
+                node.ptr
+                    .borrow()
+                    .incoming
+                    .iter()
+                    .fold(0, |mask, m| mask | 1 << indices[m])
+            })
+            .collect();
+        // Go through the prefixes by size; a prefix of size k+1 is a prefix of size k
+        // plus one node all of whose predecessors are in it.
+        let mut states = 1;
+        let mut prefixes: HashMap<u64, u128> = HashMap::new();
+        prefixes.insert(0, 1);
+        for _ in 0..n {
+            let mut next: HashMap<u64, u128> = HashMap::new();
+            for (&prefix, &count) in &prefixes {
+                for (i, &preds) in predecessors.iter().enumerate() {
+                    if prefix & 1 << i == 0 && preds & !prefix == 0 {
+                        let total = next.entry(prefix | 1 << i).or_insert(0);
+                        *total = total.checked_add(count)?;
+                    }
+                }
+            }
+            states += next.len();
+            if states > max_states {
+                return None;
+            }
+            prefixes = next;
+        }
+        Some(prefixes.values().sum())
+    }
+}
+
+// An iterator over all topological sorts of a graph; see
+// DirectedGraph::all_topological_orders.
+pub struct AllTopologicalOrders<T, E = ()> {
+    nodes: Vec<NodeRef<T, E>>,
+    successors: Vec<Vec<usize>>,
+    // The number of incoming edges of every node from nodes not yet in `chosen`.
+    in_degree: Vec<usize>,
+    used: Vec<bool>,
+    // The indices of the nodes of the current (partial) sort.
+    chosen: Vec<usize>,
+    started: bool,
+}
+impl<T, E> AllTopologicalOrders<T, E> {
+    fn choose(&mut self, i: usize) {
+        self.used[i] = true;
+        self.chosen.push(i);
+        for &j in &self.successors[i] {
+            self.in_degree[j] -= 1;
+        }
+    }
+
+    fn unchoose(&mut self) -> Option<usize> {
+        let i = self.chosen.pop()?;
+        self.used[i] = false;
+        for &j in &self.successors[i] {
+            self.in_degree[j] += 1;
+        }
+        Some(i)
+    }
+
+    // The first node after index `after` that could come next in the current sort.
+    fn next_candidate(&self, after: Option<usize>) -> Option<usize> {
+        let start = after.map_or(0, |i| i + 1);
+        (start..self.nodes.len()).find(|&i| !self.used[i] && self.in_degree[i] == 0)
+    }
+
+    // Complete the current partial sort by always choosing the first candidate.
+    // Returns false if no candidate is left before the sort is complete, which only
+    // happens if the graph has a cycle.
+    fn extend(&mut self) -> bool {
+        while self.chosen.len() < self.nodes.len() {
+            match self.next_candidate(None) {
+                Some(i) => self.choose(i),
+                None => return false,
+            }
+        }
+        true
+    }
+}
+impl<T, E> Iterator for AllTopologicalOrders<T, E> {
+    type Item = Vec<NodeRef<T, E>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if !self.started {
+            self.started = true;
+            if !self.extend() {
+                // Make sure that further calls find nothing to backtrack into.
+                while self.unchoose().is_some() {}
+                return None;
+            }
+        } else {
+            // Backtrack to the last position where a later candidate exists, take it,
+            // and complete the sort from there.
+            loop {
+                let last = self.unchoose()?;
+                if let Some(i) = self.next_candidate(Some(last)) {
+                    self.choose(i);
+                    break;
+                }
+            }
+            self.extend();
+        }
+        Some(self.chosen.iter().map(|&i| self.nodes[i].clone()).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    fn to_strings(orders: impl Iterator<Item = Vec<NodeRef<char>>>) -> Vec<String> {
+        orders
+            .map(|order| order.iter().map(|node| node.ptr.borrow().data).collect())
+            .collect()
+    }
+
+    #[test]
+    fn test_all_topological_orders() {
+        // The diamond from test_topological_sort, plus an isolated node E.
+        let mut graph = DirectedGraph::default();
+        let a = graph.add_node('A');
+        let b = graph.add_node('B');
+        let c = graph.add_node('C');
+        let d = graph.add_node('D');
+        graph.add_edge(&a, &b);
+        graph.add_edge(&a, &c);
+        graph.add_edge(&b, &d);
+        graph.add_edge(&c, &d);
+        assert_eq!(
+            to_strings(graph.all_topological_orders()),
+            vec!["ABCD", "ACBD"]
+        );
+        assert_eq!(graph.count_topological_orders(100), Some(2));
+
+        graph.add_node('E');
+        let orders = to_strings(graph.all_topological_orders());
+        assert_eq!(orders.len(), 10);
+        assert_eq!(orders[0], "ABCDE");
+        assert_eq!(orders[9], "EACBD");
+        assert_eq!(graph.count_topological_orders(100), Some(10));
+
+        graph.add_edge(&d, &a);
+        assert_eq!(graph.all_topological_orders().count(), 0);
+        assert_eq!(graph.count_topological_orders(100), Some(0));
+    }
+
+    #[test]
+    fn test_count_limits() {
+        // n independent nodes have n! sorts.
+        let mut graph = DirectedGraph::default();
+        for i in 0..12 {
+            graph.add_node(i);
+        }
+        assert_eq!(graph.all_topological_orders().take(1000).count(), 1000);
+        assert_eq!(graph.count_topological_orders(1 << 12), Some(479_001_600));
+        assert_eq!(graph.count_topological_orders(1000), None);
+
+        // A chain of 100 nodes has a single sort, but too many nodes for the count.
+        let mut graph = DirectedGraph::default();
+        let nodes: Vec<_> = (0..100).map(|i| graph.add_node(i)).collect();
+        for pair in nodes.windows(2) {
+            graph.add_edge(&pair[0], &pair[1]);
+        }
+        assert_eq!(graph.all_topological_orders().count(), 1);
+        assert_eq!(graph.count_topological_orders(usize::MAX), None);
+    }
+}
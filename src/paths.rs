@@ -0,0 +1,351 @@
+use crate::{CycleError, DirectedGraph, NodeRef};
+use std::collections::HashMap;
+use std::ops::{Add, Sub};
+
+// When a node can start in a schedule where every node takes its weight to run and
+// starts once all of its predecessors are done (plus the weight of the edge from each).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct NodeTiming<W> {
+    // The earliest time the node can start.
+    pub earliest_start: W,
+    // The latest time the node can start without delaying the whole schedule.
+    pub latest_start: W,
+    // How long the node can be delayed: latest_start - earliest_start. Zero on the
+    // critical path.
+    pub slack: W,
+}
+
+// The result of DirectedGraph::critical_path.
+pub struct CriticalPath<T, W, E = ()> {
+    // A longest path through the graph, from a node without predecessors to a node
+    // without successors, as long as no weight is negative: zero-weight nodes and edges
+    // at the end are included. Empty for an empty graph.
+    pub path: Vec<NodeRef<T, E>>,
+    // The total weight of `path`: the time the whole schedule takes.
+    pub length: W,
+    pub timings: HashMap<NodeRef<T, E>, NodeTiming<W>>,
+}
+
+// The result of DirectedGraph::dag_shortest_paths.
+pub struct ShortestPaths<T, W, E = ()> {
+    source: NodeRef<T, E>,
+    distances: HashMap<NodeRef<T, E>, W>,
+    // predecessors[v] is the node before v on a shortest path from the source to v
+    predecessors: HashMap<NodeRef<T, E>, NodeRef<T, E>>,
+}
+impl<T, W, E> ShortestPaths<T, W, E>
+where
+    W: Copy,
+{
+    pub fn source(&self) -> &NodeRef<T, E> {
+        &self.source
+    }
+
+    // The length of a shortest path from the source to `node`, or None if the source
+    // does not reach it.
+    pub fn distance(&self, node: &NodeRef<T, E>) -> Option<W> {
+        self.distances.get(node).copied()
+    }
+
+    // The node before `node` on a shortest path from the source to it. None for the
+    // source and for unreachable nodes.
+    pub fn predecessor(&self, node: &NodeRef<T, E>) -> Option<&NodeRef<T, E>> {
+        self.predecessors.get(node)
+    }
+
+    // A shortest path from the source to `node`, including both, or None if the source
+    // does not reach it.
+    pub fn path_to(&self, node: &NodeRef<T, E>) -> Option<Vec<NodeRef<T, E>>> {
+        if !self.distances.contains_key(node) {
+            return None;
+        }
+        let mut path = vec![node.clone()];
+        while let Some(pred) = self.predecessors.get(path.last().unwrap()) {
+            path.push(pred.clone());
+        }
+        path.reverse();
+        Some(path)
+    }
+}
+
+impl<T, E> DirectedGraph<T, E> {
+    // Compute shortest paths from `source` to every node it reaches, where
+    // `edge_weight` gives the length of an edge from the data of its source, the edge
+    // itself and its target, in that order. Weights
+    // may be negative. Because the graph is acyclic, relaxing the edges in topological
+    // order is enough, which takes O(V + E) time. W::default() must be zero. Fails if
+    // the graph has a cycle, even one the source does not reach. A source that is not
+    // in the graph reaches no node, not even itself.
+    pub fn dag_shortest_paths<W, F>(
+        &self,
+        source: &NodeRef<T, E>,
+        mut edge_weight: F,
+    ) -> Result<ShortestPaths<T, W, E>, CycleError<T, E>>
+    where
+        W: Copy + PartialOrd + Add<Output = W> + Default,
+        F: FnMut(&T, &E, &T) -> W,
+    {
+        let order = self.topological_order()?;
+        let mut distances = HashMap::new();
+        let mut predecessors = HashMap::new();
+        // Nodes before the source in the order cannot be reached from it.
+        if let Some(start) = order.iter().position(|n| n == source) {
+            distances.insert(source.clone(), W::default());
+            for v in &order[start..] {
+                let d = match distances.get(v) {
+                    Some(&d) => d,
+                    None => continue,
+                };
+                let v_node = v.ptr.borrow();
+                for (m, e) in v_node.outgoing_edges() {
+                    let candidate = d + edge_weight(&v_node.data, e, &m.ptr.borrow().data);
+                    let shorter = match distances.get(m) {
+                        Some(&current) => candidate < current,
+                        None => true,
+                    };
+                    if shorter {
+                        distances.insert(m.clone(), candidate);
+                        predecessors.insert(m.clone(), v.clone());
+                    }
+                }
+            }
+        }
+        Ok(ShortestPaths {
+            source: source.clone(),
+            distances,
+            predecessors,
+        })
+    }
+
+    // Find the critical path through the graph, where `node_weight` gives the time each
+    // node takes (e.g. to build). W::default() must be zero.
+    pub fn critical_path<W, F>(
+        &self,
+        node_weight: F,
+    ) -> Result<CriticalPath<T, W, E>, CycleError<T, E>>
+    where
+        W: Copy + PartialOrd + Add<Output = W> + Sub<Output = W> + Default,
+        F: FnMut(&T) -> W,
+    {
+        self.critical_path_with_edges(node_weight, |_, _, _| W::default())
+    }
+
+    // Like critical_path, but with an additional delay between the end of one node and
+    // the start of its successor, given by `edge_weight` for the data of the source, the
+    // edge and the target.
+    //
+    // A forward pass over a topological order computes the earliest starts, and a
+    // backward pass the latest starts.
+    pub fn critical_path_with_edges<W, F, G>(
+        &self,
+        mut node_weight: F,
+        mut edge_weight: G,
+    ) -> Result<CriticalPath<T, W, E>, CycleError<T, E>>
+    where
+        W: Copy + PartialOrd + Add<Output = W> + Sub<Output = W> + Default,
+        F: FnMut(&T) -> W,
+        G: FnMut(&T, &E, &T) -> W,
+    {
+        let order = self.topological_order()?;
+        let position: HashMap<_, _> = order.iter().enumerate().map(|(p, n)| (n, p)).collect();
+        let n = order.len();
+        let weight: Vec<W> = order
+            .iter()
+            .map(|v| node_weight(&v.ptr.borrow().data))
+            .collect();
+        // (successor position, edge weight) of every edge, by position of its source
+        let edges: Vec<Vec<(usize, W)>> = order
+            .iter()
+            .map(|v| {
+                let v = v.ptr.borrow();
+                v.outgoing_edges()
+                    .map(|(m, e)| (position[m], edge_weight(&v.data, e, &m.ptr.borrow().data)))
+                    .collect()
+            })
+            .collect();
+
+        // Forward pass: earliest starts, remembering which predecessor determined each.
+        let mut earliest = vec![W::default(); n];
+        let mut critical_pred: Vec<Option<usize>> = vec![None; n];
+        for p in 0..n {
+            let finish = earliest[p] + weight[p];
+            for &(q, delay) in &edges[p] {
+                if critical_pred[q].is_none() || finish + delay > earliest[q] {
+                    earliest[q] = finish + delay;
+                    critical_pred[q] = Some(p);
+                }
+            }
+        }
+        // Of the nodes that finish last, prefer one without successors, so that the path
+        // runs on through successors that take no time.
+        let mut last: Option<usize> = None;
+        let mut length = W::default();
+        for p in 0..n {
+            let finish = earliest[p] + weight[p];
+            let better = match last {
+                None => true,
+                Some(l) => {
+                    finish > length
+                        || (finish == length && edges[p].is_empty() && !edges[l].is_empty())
+                }
+            };
+            if better {
+                last = Some(p);
+                length = finish;
+            }
+        }
+
+        // Backward pass: latest starts.
+        let mut latest: Vec<W> = (0..n).map(|p| length - weight[p]).collect();
+        for p in (0..n).rev() {
+            for &(q, delay) in &edges[p] {
+                let start = latest[q] - delay - weight[p];
+                if start < latest[p] {
+                    latest[p] = start;
+                }
+            }
+        }
+
+        let mut path = Vec::new();
+        let mut p = last;
+        while let Some(q) = p {
+            path.push(order[q].clone());
+            p = critical_pred[q];
+        }
+        path.reverse();
+        let timings = (0..n)
+            .map(|p| {
+                let timing = NodeTiming {
+                    earliest_start: earliest[p],
+                    latest_start: latest[p],
+                    slack: latest[p] - earliest[p],
+                };
+                (order[p].clone(), timing)
+            })
+            .collect();
+        Ok(CriticalPath {
+            path,
+            length,
+            timings,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    fn to_string(nodes: &[NodeRef<(char, u32)>]) -> String {
+        nodes.iter().map(|node| node.ptr.borrow().data.0).collect()
+    }
+
+    #[test]
+    fn test_critical_path() {
+        //      B(3)
+        //     /    \
+        // A(2)      D(1)
+        //     \    /
+        //      C(5)   E(4)
+        let mut graph = DirectedGraph::default();
+        let a = graph.add_node(('A', 2));
+        let b = graph.add_node(('B', 3));
+        let c = graph.add_node(('C', 5));
+        let d = graph.add_node(('D', 1));
+        let e = graph.add_node(('E', 4));
+        graph.add_edge(&a, &b);
+        graph.add_edge(&a, &c);
+        graph.add_edge(&b, &d);
+        graph.add_edge(&c, &d);
+
+        let result = graph.critical_path(|&(_, w)| w).ok().unwrap();
+        assert_eq!(to_string(&result.path), "ACD");
+        assert_eq!(result.length, 8);
+        let timing = |node| result.timings[node];
+        assert_eq!(
+            timing(&b),
+            NodeTiming {
+                earliest_start: 2,
+                latest_start: 4,
+                slack: 2
+            }
+        );
+        assert_eq!(timing(&d).earliest_start, 7);
+        assert_eq!(timing(&d).slack, 0);
+        assert_eq!(timing(&e).slack, 4);
+
+        // A 3 unit delay on B -> D makes the other branch critical.
+        let result = graph
+            .critical_path_with_edges(
+                |&(_, w)| w as f64,
+                |from, _, to| {
+                    if (from.0, to.0) == ('B', 'D') {
+                        3.0
+                    } else {
+                        0.0
+                    }
+                },
+            )
+            .ok()
+            .unwrap();
+        assert_eq!(to_string(&result.path), "ABD");
+        assert_eq!(result.length, 9.0);
+        assert_eq!(result.timings[&c].slack, 1.0);
+
+        // A successor that takes no time still ends the path.
+        let f = graph.add_node(('F', 0));
+        graph.add_edge(&d, &f);
+        let result = graph.critical_path(|&(_, w)| w).ok().unwrap();
+        assert_eq!(to_string(&result.path), "ACDF");
+        assert_eq!(result.length, 8);
+
+        graph.add_edge(&d, &a);
+        assert!(graph.critical_path(|&(_, w)| w).is_err());
+    }
+
+    #[test]
+    fn test_dag_shortest_paths() {
+        // S -> A -> C -> T, S -> B -> C, B -> T, A -> B, and X -> S, weighted by their
+        // edge data.
+        let mut graph = DirectedGraph::<char, i32>::new();
+        let x = graph.add_node('X');
+        let s = graph.add_node('S');
+        let a = graph.add_node('A');
+        let b = graph.add_node('B');
+        let c = graph.add_node('C');
+        let t = graph.add_node('T');
+        for (from, to, weight) in [
+            (&x, &s, 1),
+            (&s, &a, 2),
+            (&s, &b, 6),
+            (&a, &b, 3),
+            (&a, &c, 7),
+            (&b, &c, -4),
+            (&b, &t, 2),
+            (&c, &t, 1),
+        ] {
+            graph.add_edge_with(from, to, weight);
+        }
+        let paths = graph.dag_shortest_paths(&s, |_, &w, _| w).ok().unwrap();
+        assert_eq!(paths.distance(&s), Some(0));
+        assert_eq!(paths.distance(&b), Some(5));
+        assert_eq!(paths.distance(&c), Some(1));
+        assert_eq!(paths.distance(&t), Some(2));
+        assert_eq!(paths.distance(&x), None);
+        assert_eq!(paths.predecessor(&c), Some(&b));
+        assert_eq!(paths.predecessor(&s), None);
+        let path: String = paths
+            .path_to(&t)
+            .unwrap()
+            .iter()
+            .map(|n| n.ptr.borrow().data)
+            .collect();
+        assert_eq!(path, "SABCT");
+        assert!(paths.path_to(&x).is_none());
+        assert_eq!(paths.path_to(&s), Some(vec![s.clone()]));
+
+        let other = DirectedGraph::<char, i32>::new().add_node('S');
+        let paths = graph.dag_shortest_paths(&other, |_, &w, _| w).ok().unwrap();
+        assert_eq!(paths.distance(&other), None);
+        assert_eq!(paths.distance(&t), None);
+    }
+}
@@ -0,0 +1,92 @@
+use crate::visit::{Bfs, Direction};
+use crate::{DirectedGraph, NodeRef};
+
+// Reachability queries, answered by breadth-first search over the `incoming` and
+// `outgoing` lists. A node is a descendant of another if it can be reached from it
+// through at least one edge, so a node is only its own descendant (and ancestor) if it
+// lies on a cycle.
+impl<T, E> DirectedGraph<T, E> {
+    // Everything reachable from `node`: what depends on it, if edges point from a
+    // dependency to its dependents.
+    pub fn descendants(&self, node: &NodeRef<T, E>) -> Bfs<T, E> {
+        self.descendants_of_all(std::slice::from_ref(node))
+    }
+
+    // Everything that can reach `node`.
+    pub fn ancestors(&self, node: &NodeRef<T, E>) -> Bfs<T, E> {
+        self.ancestors_of_all(std::slice::from_ref(node))
+    }
+
+    // Everything reachable from at least one of `nodes`, each node reported once.
+    pub fn descendants_of_all(&self, nodes: &[NodeRef<T, E>]) -> Bfs<T, E> {
+        Self::strictly_reachable(nodes, Direction::Outgoing)
+    }
+
+    // Everything that can reach at least one of `nodes`, each node reported once.
+    pub fn ancestors_of_all(&self, nodes: &[NodeRef<T, E>]) -> Bfs<T, E> {
+        Self::strictly_reachable(nodes, Direction::Incoming)
+    }
+
+    // Whether there is a path from `from` to `to`. Every node reaches itself. The
+    // search stops as soon as `to` is found.
+    pub fn is_reachable(&self, from: &NodeRef<T, E>, to: &NodeRef<T, E>) -> bool {
+        Bfs::new(std::slice::from_ref(from), Direction::Outgoing).any(|n| n == *to)
+    }
+
+    // Start the search from the neighbours of `nodes`, so that `nodes` themselves are
+    // only reported if they can be reached through an edge.
+    fn strictly_reachable(nodes: &[NodeRef<T, E>], direction: Direction) -> Bfs<T, E> {
+        let starts: Vec<_> = nodes
+            .iter()
+            .flat_map(|node| {
+                let node = node.ptr.borrow();
+                match direction {
+                    Direction::Outgoing => node.outgoing.clone(),
+                    Direction::Incoming => node.incoming.clone(),
+                }
+            })
+            .collect();
+        Bfs::new(&starts, direction)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    use std::collections::BTreeSet;
+
+    fn to_set(nodes: impl Iterator<Item = NodeRef<char>>) -> BTreeSet<char> {
+        nodes.map(|node| node.ptr.borrow().data).collect()
+    }
+
+    #[test]
+    fn test_ancestors_and_descendants() {
+        // A -> B -> C -> D, A -> D, E -> C, and a cycle F -> G -> F.
+        let mut graph = DirectedGraph::default();
+        let n: Vec<_> = ('A'..='G').map(|c| graph.add_node(c)).collect();
+        for &(from, to) in &[(0, 1), (1, 2), (2, 3), (0, 3), (4, 2), (5, 6), (6, 5)] {
+            graph.add_edge(&n[from], &n[to]);
+        }
+        let set = |s: &str| s.chars().collect::<BTreeSet<_>>();
+
+        assert_eq!(to_set(graph.descendants(&n[1])), set("CD"));
+        assert_eq!(to_set(graph.ancestors(&n[2])), set("ABE"));
+        assert_eq!(to_set(graph.ancestors(&n[0])), set(""));
+        assert_eq!(to_set(graph.descendants(&n[5])), set("FG"));
+        assert_eq!(
+            to_set(graph.descendants_of_all(&[n[1].clone(), n[4].clone()])),
+            set("CD")
+        );
+        assert_eq!(
+            to_set(graph.ancestors_of_all(&[n[1].clone(), n[4].clone()])),
+            set("A")
+        );
+        // Each node is reported once.
+        assert_eq!(graph.ancestors(&n[3]).count(), 4);
+
+        assert!(graph.is_reachable(&n[0], &n[3]));
+        assert!(graph.is_reachable(&n[4], &n[4]));
+        assert!(!graph.is_reachable(&n[3], &n[0]));
+        assert!(!graph.is_reachable(&n[0], &n[4]));
+    }
+}
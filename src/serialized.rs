@@ -0,0 +1,328 @@
+// A representation of a DirectedGraph without pointers, for persisting it: a list of
+// node data and a list of edges between node indices.
+//
+// NodeRef identity is a pointer, so a graph cannot be written out as it is. Converting
+// to a SerializedGraph and back gives a graph with the same nodes in the same order,
+// and the same edges in the same order within every `incoming` and `outgoing` list.
+//
+// With the `serde` feature, SerializedGraph implements Serialize and Deserialize, and so
+// does DirectedGraph, by going through a SerializedGraph.
+
+use crate::{DirectedGraph, NodeRef};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::rc::Rc;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SerializedGraph<T, E = ()> {
+    // The data of every node, in insertion order.
+    pub nodes: Vec<T>,
+    // Every edge, grouped by source node and in `outgoing` order within each group.
+    pub edges: Vec<SerializedEdge<E>>,
+    // incoming[i] lists the edges into node i in `incoming` order, as indices into
+    // `edges`.
+    pub incoming: Vec<Vec<usize>>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SerializedEdge<E = ()> {
+    // The indices of the source and target nodes.
+    pub from: usize,
+    pub to: usize,
+    pub data: E,
+}
+
+// The error returned for a SerializedGraph that does not describe a graph.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SerializedGraphError {
+    // An edge refers to a node index that does not exist.
+    NodeOutOfRange { edge: usize, node: usize },
+    // `incoming` does not have one list per node.
+    IncomingLength { expected: usize, found: usize },
+    // The incoming list of a node is not an ordering of exactly the edges into it.
+    IncomingMismatch { node: usize },
+}
+
+impl fmt::Display for SerializedGraphError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SerializedGraphError::NodeOutOfRange { edge, node } => {
+                write!(
+                    f,
+                    "edge {} refers to node {}, which does not exist",
+                    edge, node
+                )
+            }
+            SerializedGraphError::IncomingLength { expected, found } => write!(
+                f,
+                "expected incoming edge lists for {} nodes, found {}",
+                expected, found
+            ),
+            SerializedGraphError::IncomingMismatch { node } => write!(
+                f,
+                "the incoming edge list of node {} does not match its edges",
+                node
+            ),
+        }
+    }
+}
+
+impl Error for SerializedGraphError {}
+
+// Serializing copies the data out of the nodes and edges, as to_serialized does.
+#[cfg(feature = "serde")]
+impl<T, E> Serialize for DirectedGraph<T, E>
+where
+    T: Clone + Serialize,
+    E: Clone + Serialize,
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.to_serialized().serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, T, E> Deserialize<'de> for DirectedGraph<T, E>
+where
+    T: Deserialize<'de>,
+    E: Deserialize<'de>,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let serialized = SerializedGraph::deserialize(deserializer)?;
+        DirectedGraph::from_serialized(serialized).map_err(serde::de::Error::custom)
+    }
+}
+
+impl<T, E> DirectedGraph<T, E> {
+    // Copy the graph into its serialized form.
+    pub fn to_serialized(&self) -> SerializedGraph<T, E>
+    where
+        T: Clone,
+        E: Clone,
+    {
+        let indices = self.node_indices();
+        let mut edges = Vec::new();
+        // Both ends of an edge share its data, which identifies the edge among parallel
+        // ones.
+        let mut edge_indices = HashMap::new();
+        for (i, node) in self.nodes.iter().enumerate() {
+            let node = node.ptr.borrow();
+            for (m, data) in node.outgoing.iter().zip(&node.outgoing_data) {
+                edge_indices.insert(Rc::as_ptr(data), edges.len());
+                edges.push(SerializedEdge {
+                    from: i,
+                    to: indices[m],
+                    data: E::clone(data),
+                });
+            }
+        }
+        SerializedGraph {
+            nodes: self
+                .nodes
+                .iter()
+                .map(|node| node.ptr.borrow().data.clone())
+                .collect(),
+            edges,
+            incoming: self
+                .nodes
+                .iter()
+                .map(|node| {
+                    let node = node.ptr.borrow();
+                    node.incoming_data
+                        .iter()
+                        .map(|data| edge_indices[&Rc::as_ptr(data)])
+                        .collect()
+                })
+                .collect(),
+        }
+    }
+
+    // Rebuild a graph from its serialized form, checking that the form is consistent.
+    pub fn from_serialized(
+        serialized: SerializedGraph<T, E>,
+    ) -> Result<Self, SerializedGraphError> {
+        let SerializedGraph {
+            nodes,
+            edges,
+            incoming,
+        } = serialized;
+        let n = nodes.len();
+        for (k, edge) in edges.iter().enumerate() {
+            for node in [edge.from, edge.to] {
+                if node >= n {
+                    return Err(SerializedGraphError::NodeOutOfRange { edge: k, node });
+                }
+            }
+        }
+        if incoming.len() != n {
+            return Err(SerializedGraphError::IncomingLength {
+                expected: n,
+                found: incoming.len(),
+            });
+        }
+        // Every edge must appear exactly once, in the incoming list of its target.
+        let mut listed = vec![false; edges.len()];
+        for (i, list) in incoming.iter().enumerate() {
+            for &k in list {
+                if k >= edges.len() || edges[k].to != i || listed[k] {
+                    return Err(SerializedGraphError::IncomingMismatch { node: i });
+                }
+                listed[k] = true;
+            }
+        }
+        if let Some(k) = listed.iter().position(|&listed| !listed) {
+            return Err(SerializedGraphError::IncomingMismatch { node: edges[k].to });
+        }
+
+        let mut graph = DirectedGraph::new();
+        let nodes: Vec<NodeRef<T, E>> =
+            nodes.into_iter().map(|data| graph.add_node(data)).collect();
+        let edges: Vec<_> = edges
+            .into_iter()
+            .map(|edge| (edge.from, edge.to, Rc::new(edge.data)))
+            .collect();
+        for (from, to, data) in &edges {
+            let mut node = nodes[*from].ptr.borrow_mut();
+            node.outgoing.push(nodes[*to].clone());
+            node.outgoing_data.push(data.clone());
+        }
+        for (node, list) in nodes.iter().zip(incoming) {
+            let mut node = node.ptr.borrow_mut();
+            for k in list {
+                let (from, _, data) = &edges[k];
+                node.incoming.push(nodes[*from].clone());
+                node.incoming_data.push(data.clone());
+            }
+        }
+        Ok(graph)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    fn adjacency(graph: &DirectedGraph<char, u32>) -> Vec<String> {
+        let list = |edges: &mut dyn Iterator<Item = (&NodeRef<char, u32>, &u32)>| {
+            edges
+                .map(|(m, e)| format!("{}{}", m.ptr.borrow().data, e))
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+        graph
+            .nodes()
+            .iter()
+            .map(|node| {
+                let node = node.ptr.borrow();
+                format!(
+                    "{}: in {} / out {}",
+                    node.data,
+                    list(&mut node.incoming_edges()),
+                    list(&mut node.outgoing_edges())
+                )
+            })
+            .collect()
+    }
+
+    // Parallel edges, a self loop and a removed edge, with the edge data telling the edges
+    // apart. The incoming order of X differs from the order of the edges by source.
+    fn graph() -> DirectedGraph<char, u32> {
+        let mut graph = DirectedGraph::new();
+        let a = graph.add_node('A');
+        let b = graph.add_node('B');
+        let x = graph.add_node('X');
+        let y = graph.add_node('Y');
+        graph.add_edge_with(&b, &y, 0);
+        graph.add_edge_with(&x, &x, 5);
+        graph.add_edge_with(&a, &x, 1);
+        graph.add_edge_with(&a, &y, 2);
+        graph.add_edge_with(&b, &y, 3);
+        graph.add_edge_with(&b, &x, 4);
+        assert!(graph.remove_edge(&b, &y));
+        graph
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let graph = graph();
+        let serialized = graph.to_serialized();
+        assert_eq!(serialized.nodes, vec!['A', 'B', 'X', 'Y']);
+        assert_eq!(
+            serialized.edges[0],
+            SerializedEdge {
+                from: 0,
+                to: 2,
+                data: 1
+            }
+        );
+        assert_eq!(serialized.incoming[2], vec![4, 0, 3]);
+        let copy = DirectedGraph::from_serialized(serialized.clone()).unwrap();
+        assert_eq!(adjacency(&copy), adjacency(&graph));
+        assert_eq!(copy.to_serialized(), serialized);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_json_round_trip() {
+        let graph = graph();
+        let json = serde_json::to_string(&graph).unwrap();
+        assert_eq!(
+            json,
+            "{\"nodes\":[\"A\",\"B\",\"X\",\"Y\"],\"edges\":[\
+             {\"from\":0,\"to\":2,\"data\":1},{\"from\":0,\"to\":3,\"data\":2},\
+             {\"from\":1,\"to\":3,\"data\":3},{\"from\":1,\"to\":2,\"data\":4},\
+             {\"from\":2,\"to\":2,\"data\":5}],\
+             \"incoming\":[[],[],[4,0,3],[1,2]]}"
+        );
+        let copy: DirectedGraph<char, u32> = serde_json::from_str(&json).unwrap();
+        assert_eq!(adjacency(&copy), adjacency(&graph));
+
+        let invalid = r#"{"nodes":["A"],"edges":[{"from":0,"to":1,"data":0}],"incoming":[[]]}"#;
+        let error = serde_json::from_str::<DirectedGraph<char, u32>>(invalid)
+            .err()
+            .unwrap();
+        assert!(error.to_string().starts_with("edge 0 refers to node 1"));
+    }
+
+    #[test]
+    fn test_invalid() {
+        let edge = |from, to| SerializedEdge { from, to, data: () };
+        let check = |edges, incoming| {
+            let serialized = SerializedGraph {
+                nodes: vec!['A', 'B'],
+                edges,
+                incoming,
+            };
+            DirectedGraph::from_serialized(serialized).err().unwrap()
+        };
+        assert_eq!(
+            check(vec![edge(0, 2)], vec![vec![], vec![]]),
+            SerializedGraphError::NodeOutOfRange { edge: 0, node: 2 }
+        );
+        assert_eq!(
+            check(vec![edge(0, 1)], vec![vec![0]]),
+            SerializedGraphError::IncomingLength {
+                expected: 2,
+                found: 1
+            }
+        );
+        assert_eq!(
+            check(vec![edge(0, 1)], vec![vec![0], vec![]]),
+            SerializedGraphError::IncomingMismatch { node: 0 }
+        );
+        assert_eq!(
+            check(vec![edge(0, 1)], vec![vec![], vec![]]),
+            SerializedGraphError::IncomingMismatch { node: 1 }
+        );
+        assert_eq!(
+            check(vec![edge(0, 1)], vec![vec![], vec![0, 0]]),
+            SerializedGraphError::IncomingMismatch { node: 1 }
+        );
+    }
+}
@@ -0,0 +1,283 @@
+// A thread-safe counterpart of DirectedGraph.
+//
+// Nodes are shared through Arc<RwLock<..>> instead of Rc<RefCell<..>>, so a
+// SyncDirectedGraph<T> and its SyncNodeRef<T>s are Send and Sync whenever T is.
+// The API mirrors DirectedGraph; a poisoned lock is treated like a failed RefCell
+// borrow and panics.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::error::Error;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, RwLock};
+
+pub struct SyncNode<T> {
+    pub data: T,
+    pub incoming: Vec<SyncNodeRef<T>>,
+    pub outgoing: Vec<SyncNodeRef<T>>,
+    // The id of the SyncDirectedGraph the node belongs to.
+    graph: usize,
+}
+
+pub struct SyncNodeRef<T> {
+    pub ptr: Arc<RwLock<SyncNode<T>>>,
+}
+impl<T> SyncNodeRef<T> {
+    fn new(data: T, graph: usize) -> SyncNodeRef<T> {
+        SyncNodeRef {
+            ptr: Arc::new(RwLock::new(SyncNode {
+                data,
+                incoming: Vec::new(),
+                outgoing: Vec::new(),
+                graph,
+            })),
+        }
+    }
+}
+impl<T> Clone for SyncNodeRef<T> {
+    fn clone(&self) -> SyncNodeRef<T> {
+        SyncNodeRef {
+            ptr: self.ptr.clone(),
+        }
+    }
+}
+
+// Reference equality semantics for SyncNodeRef<T>.
+impl<T> Eq for SyncNodeRef<T> {}
+impl<T> PartialEq for SyncNodeRef<T> {
+    fn eq(&self, rhs: &SyncNodeRef<T>) -> bool {
+        Arc::ptr_eq(&self.ptr, &rhs.ptr)
+    }
+}
+impl<T> Hash for SyncNodeRef<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        Arc::as_ptr(&self.ptr).hash(state)
+    }
+}
+
+impl<T> fmt::Debug for SyncNodeRef<T>
+where
+    T: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("SyncNodeRef")
+            .field(&self.ptr.read().unwrap().data)
+            .finish()
+    }
+}
+
+pub struct SyncDirectedGraph<T> {
+    id: usize,
+    nodes: Vec<SyncNodeRef<T>>,
+}
+impl<T> Default for SyncDirectedGraph<T> {
+    fn default() -> Self {
+        SyncDirectedGraph {
+            id: crate::NEXT_GRAPH_ID.fetch_add(1, Ordering::Relaxed),
+            nodes: Vec::new(),
+        }
+    }
+}
+impl<T> SyncDirectedGraph<T> {
+    // Whether `node` was added to this graph.
+    pub fn contains(&self, node: &SyncNodeRef<T>) -> bool {
+        node.ptr.read().unwrap().graph == self.id
+    }
+
+    pub fn add_node(&mut self, data: T) -> SyncNodeRef<T> {
+        let result = SyncNodeRef::new(data, self.id);
+        self.nodes.push(result.clone());
+        result
+    }
+
+    // Returns false without adding the edge if either end is not a node of this graph.
+    pub fn add_edge(&mut self, from: &SyncNodeRef<T>, to: &SyncNodeRef<T>) -> bool {
+        if !self.contains(from) || !self.contains(to) {
+            return false;
+        }
+        from.ptr.write().unwrap().outgoing.push(to.clone());
+        to.ptr.write().unwrap().incoming.push(from.clone());
+        true
+    }
+
+    // Compute a topological sort, consuming the graph. Provided for parity with
+    // DirectedGraph::topological_sort; see topological_order.
+    pub fn topological_sort(self) -> Result<Vec<SyncNodeRef<T>>, CycleError<T>> {
+        self.topological_order()
+    }
+
+    // Compute a topological sort using Kahn's algorithm without modifying the graph,
+    // so it can run concurrently from several threads holding a shared reference.
+    pub fn topological_order(&self) -> Result<Vec<SyncNodeRef<T>>, CycleError<T>> {
+        let indices: HashMap<_, _> = self
+            .nodes
+            .iter()
+            .enumerate()
+            .map(|(i, n)| (n.clone(), i))
+            .collect();
+        let mut in_degree: Vec<usize> = self
+            .nodes
+            .iter()
+            .map(|n| n.ptr.read().unwrap().incoming.len())
+            .collect();
+        let mut s: VecDeque<usize> = (0..self.nodes.len())
+            .filter(|&i| in_degree[i] == 0)
+            .collect();
+        let mut result = Vec::with_capacity(self.nodes.len());
+        while let Some(i) = s.pop_front() {
+            let n = &self.nodes[i];
+            result.push(n.clone());
+            for m in &n.ptr.read().unwrap().outgoing {
+                let j = indices[m];
+                in_degree[j] -= 1;
+                if in_degree[j] == 0 {
+                    s.push_back(j);
+                }
+            }
+        }
+        if result.len() == self.nodes.len() {
+            Ok(result)
+        } else {
+            Err(self.cycle_error(&result))
+        }
+    }
+
+    // See DirectedGraph::cycle_error.
+    fn cycle_error(&self, ordered: &[SyncNodeRef<T>]) -> CycleError<T> {
+        let ordered: HashSet<_> = ordered.iter().collect();
+        let unordered: Vec<_> = self
+            .nodes
+            .iter()
+            .filter(|n| !ordered.contains(n))
+            .cloned()
+            .collect();
+        let mut path = Vec::new();
+        let mut position = HashMap::new();
+        let mut n = unordered[0].clone();
+        while !position.contains_key(&n) {
+            position.insert(n.clone(), path.len());
+            path.push(n.clone());
+            let pred = n
+                .ptr
+                .read()
+                .unwrap()
+                .incoming
+                .iter()
+                .find(|m| !ordered.contains(m))
+                .unwrap()
+                .clone();
+            n = pred;
+        }
+        let mut cycle = path.split_off(position[&n]);
+        cycle.reverse();
+        cycle.rotate_right(1);
+        CycleError { cycle, unordered }
+    }
+}
+
+// See the Drop impl of DirectedGraph.
+impl<T> Drop for SyncDirectedGraph<T> {
+    fn drop(&mut self) {
+        for node in &self.nodes {
+            // Tear the edges down even if another thread panicked while holding the lock.
+            let mut n = match node.ptr.write() {
+                Ok(n) => n,
+                Err(poisoned) => poisoned.into_inner(),
+            };
+            n.incoming.clear();
+            n.outgoing.clear();
+        }
+    }
+}
+
+// The SyncDirectedGraph counterpart of crate::CycleError.
+#[derive(Debug)]
+pub struct CycleError<T> {
+    // The nodes of one concrete cycle, in edge order. The closing edge goes from the
+    // last node back to the first one.
+    pub cycle: Vec<SyncNodeRef<T>>,
+    // All nodes that could not be ordered, in insertion order.
+    pub unordered: Vec<SyncNodeRef<T>>,
+}
+
+impl<T> fmt::Display for CycleError<T>
+where
+    T: fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "cycle detected: ")?;
+        for (i, node) in self.cycle.iter().chain(self.cycle.first()).enumerate() {
+            if i > 0 {
+                write!(f, " -> ")?;
+            }
+            write!(f, "{}", node.ptr.read().unwrap().data)?;
+        }
+        Ok(())
+    }
+}
+
+impl<T> Error for CycleError<T> where T: fmt::Debug + fmt::Display {}
+
+#[cfg(test)]
+mod tests {
+    use crate::sync::*;
+    use std::sync::Mutex;
+    use std::thread;
+
+    fn to_string(nodes: &[SyncNodeRef<u32>]) -> String {
+        nodes
+            .iter()
+            .map(|n| n.ptr.read().unwrap().data.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_build_and_sort_from_threads() {
+        // Build a chain 0 -> 1 -> ... -> 7, with the edges added from different threads.
+        let mut graph = SyncDirectedGraph::default();
+        let nodes: Vec<_> = (0..8).map(|i| graph.add_node(i)).collect();
+        let graph = Mutex::new(graph);
+        thread::scope(|scope| {
+            for pair in nodes.windows(2) {
+                let graph = &graph;
+                scope.spawn(move || graph.lock().unwrap().add_edge(&pair[0], &pair[1]));
+            }
+        });
+        let graph = graph.into_inner().unwrap();
+
+        // Sort it from several threads at once through a shared reference.
+        let orders: Vec<_> = thread::scope(|scope| {
+            let handles: Vec<_> = (0..4)
+                .map(|_| scope.spawn(|| graph.topological_order().unwrap()))
+                .collect();
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        });
+        for order in orders {
+            assert_eq!(to_string(&order), "01234567");
+        }
+
+        // Node handles can be sent to other threads too.
+        let last = nodes[7].clone();
+        let data = thread::spawn(move || last.ptr.read().unwrap().data)
+            .join()
+            .unwrap();
+        assert_eq!(data, 7);
+    }
+
+    #[test]
+    fn test_cycle_error() {
+        let mut graph = SyncDirectedGraph::default();
+        let a = graph.add_node(1);
+        let b = graph.add_node(2);
+        graph.add_edge(&a, &b);
+        graph.add_edge(&b, &a);
+        // Nodes of other graphs cannot be connected.
+        assert!(!graph.add_edge(&a, &SyncDirectedGraph::default().add_node(3)));
+        let err = thread::spawn(move || graph.topological_sort().err().unwrap())
+            .join()
+            .unwrap();
+        assert_eq!(err.cycle, vec![a, b]);
+        assert_eq!(err.to_string(), "cycle detected: 1 -> 2 -> 1");
+    }
+}
@@ -0,0 +1,403 @@
+use crate::bitset::BitSet;
+use crate::{CycleError, DirectedGraph, NodeRef};
+use std::collections::{HashMap, VecDeque};
+
+impl<T, E> DirectedGraph<T, E> {
+    // Remove every edge that is implied by the others: an edge from A to C is removed if
+    // there is a longer path from A to C, and parallel edges are merged into one. The
+    // remaining edges keep their relative order. Every pair of nodes stays connected by
+    // a path if and only if it was before, and no edge can be removed without breaking
+    // that. Fails without changing the graph if it has a cycle.
+    //
+    // This keeps a set of descendants per node, which takes O(n^2) bits of memory.
+    pub fn transitive_reduction(&mut self) -> Result<(), CycleError<T, E>> {
+        let order = self.topological_order()?;
+        let position: HashMap<_, _> = order.iter().enumerate().map(|(p, n)| (n, p)).collect();
+        // descendants[p] is the set of positions of the nodes reachable from order[p]
+        let mut descendants = vec![BitSet::new(0); order.len()];
+        let mut redundant = Vec::new();
+        for (p, node) in order.iter().enumerate().rev() {
+            let outgoing = node.ptr.borrow().outgoing.clone();
+            let mut targets: Vec<usize> = outgoing.iter().map(|m| position[m]).collect();
+            targets.sort_unstable();
+            // If some successor reaches a later one, it comes earlier in the order, so
+            // by the time we look at a successor we know whether it is reachable from
+            // the ones before it.
+            let mut reachable = BitSet::new(order.len());
+            for (k, &q) in targets.iter().enumerate() {
+                if reachable.contains(q) || (k > 0 && targets[k - 1] == q) {
+                    redundant.push((node.clone(), order[q].clone()));
+                } else {
+                    reachable.insert(q);
+                    reachable.union_with(&descendants[q]);
+                }
+            }
+            descendants[p] = reachable;
+        }
+        for (from, to) in redundant {
+            // Of several parallel edges, remove_edge drops the first ones, so the one
+            // that is kept is the last of them.
+            self.remove_edge(&from, &to);
+        }
+        Ok(())
+    }
+
+    // Add an edge from every node to each of its descendants that is not already a
+    // direct successor, so that reachability becomes adjacency. The new edges get
+    // default data. Each node's new edges are added in topological order of their
+    // targets, after its existing edges. Fails without changing the graph if it has a
+    // cycle.
+    pub fn transitive_closure(&mut self) -> Result<(), CycleError<T, E>>
+    where
+        E: Default,
+    {
+        let order = self.topological_order()?;
+        let position = positions(&order);
+        let descendants = descendant_sets(&order, &position);
+        for (p, from) in order.iter().enumerate() {
+            let mut targets = descendants[p].clone();
+            for to in &from.ptr.borrow().outgoing {
+                targets.remove(position[to]);
+            }
+            for q in targets.iter() {
+                self.add_edge(from, &order[q]);
+            }
+        }
+        Ok(())
+    }
+
+    // Precompute the answer to every reachability question, as one bit per pair of
+    // nodes. Fails if the graph has a cycle.
+    pub fn reachability_matrix(&self) -> Result<ReachabilityMatrix<T, E>, CycleError<T, E>> {
+        let order = self.topological_order()?;
+        let position = positions(&order);
+        let rows = descendant_sets(&order, &position);
+        Ok(ReachabilityMatrix { position, rows })
+    }
+
+    // Build a ChainIndex for reachability questions. Fails if the graph has a cycle.
+    pub fn chain_index(&self) -> Result<ChainIndex<T, E>, CycleError<T, E>> {
+        let order = self.topological_order()?;
+        let position = positions(&order);
+        let successor = path_cover(&order, &position);
+        // Number the chains by their first node, and the nodes along each chain.
+        let mut chain_of = vec![(0, 0); order.len()];
+        let mut is_first = vec![true; order.len()];
+        for &q in successor.iter().flatten() {
+            is_first[q] = false;
+        }
+        let mut chains = 0;
+        for p in (0..order.len()).filter(|&p| is_first[p]) {
+            let mut next = Some(p);
+            let mut index = 0;
+            while let Some(q) = next {
+                chain_of[q] = (chains, index);
+                index += 1;
+                next = successor[q];
+            }
+            chains += 1;
+        }
+        // first_reached[p * chains + c] is the index of the first node on chain c that
+        // order[p] reaches, if any. Everything after it on the chain is reached too.
+        let mut first_reached = vec![u32::MAX; order.len() * chains];
+        for (p, node) in order.iter().enumerate().rev() {
+            let (chain, index) = chain_of[p];
+            first_reached[p * chains + chain] = index;
+            for m in &node.ptr.borrow().outgoing {
+                let q = position[m];
+                for c in 0..chains {
+                    let reached = first_reached[q * chains + c];
+                    let entry = &mut first_reached[p * chains + c];
+                    *entry = (*entry).min(reached);
+                }
+            }
+        }
+        Ok(ChainIndex {
+            position,
+            chain_of,
+            first_reached,
+            chains,
+        })
+    }
+}
+
+// A minimum cover of the graph with disjoint paths, as the successor of every node on
+// its path, by position in `order`. A path cover with k paths uses n - k edges, each
+// node having at most one path edge in and one out, so a minimum cover is a maximum
+// matching between nodes as edge sources and nodes as edge targets. That is found with
+// the Hopcroft-Karp algorithm in O(E * sqrt(V)) time.
+fn path_cover<T, E>(
+    order: &[NodeRef<T, E>],
+    position: &HashMap<NodeRef<T, E>, usize>,
+) -> Vec<Option<usize>> {
+    const UNREACHED: usize = usize::MAX;
+    let n = order.len();
+    let successors: Vec<Vec<usize>> = order
+        .iter()
+        .map(|node| {
+            let mut targets: Vec<usize> = node
+                .ptr
+                .borrow()
+                .outgoing
+                .iter()
+                .map(|m| position[m])
+                .collect();
+            targets.sort_unstable();
+            targets.dedup();
+            targets
+        })
+        .collect();
+    let mut successor: Vec<Option<usize>> = vec![None; n];
+    let mut predecessor: Vec<Option<usize>> = vec![None; n];
+    let mut distance = vec![UNREACHED; n];
+    loop {
+        // Find the length of the shortest augmenting paths, layering the sources by
+        // their distance from an unmatched source.
+        let mut queue: VecDeque<usize> = (0..n).filter(|&p| successor[p].is_none()).collect();
+        for p in 0..n {
+            distance[p] = if successor[p].is_none() { 0 } else { UNREACHED };
+        }
+        let mut found = false;
+        while let Some(p) = queue.pop_front() {
+            for &q in &successors[p] {
+                match predecessor[q] {
+                    None => found = true,
+                    Some(r) if distance[r] == UNREACHED => {
+                        distance[r] = distance[p] + 1;
+                        queue.push_back(r);
+                    }
+                    Some(_) => {}
+                }
+            }
+        }
+        if !found {
+            return successor;
+        }
+        // Augment along vertex-disjoint shortest paths, with an explicit stack so that
+        // long paths cannot overflow the call stack. next[p] is the next edge of p to try.
+        let mut next = vec![0; n];
+        for start in 0..n {
+            if successor[start].is_some() {
+                continue;
+            }
+            let mut stack = vec![start];
+            while let Some(&p) = stack.last() {
+                let q = match successors[p].get(next[p]) {
+                    Some(&q) => q,
+                    None => {
+                        // Dead end: no augmenting path continues through p.
+                        distance[p] = UNREACHED;
+                        stack.pop();
+                        if let Some(&r) = stack.last() {
+                            next[r] += 1;
+                        }
+                        continue;
+                    }
+                };
+                match predecessor[q] {
+                    None => {
+                        for &r in &stack {
+                            let q = successors[r][next[r]];
+                            successor[r] = Some(q);
+                            predecessor[q] = Some(r);
+                        }
+                        break;
+                    }
+                    Some(r) if distance[r] == distance[p] + 1 => stack.push(r),
+                    Some(_) => next[p] += 1,
+                }
+            }
+        }
+    }
+}
+
+fn positions<T, E>(order: &[NodeRef<T, E>]) -> HashMap<NodeRef<T, E>, usize> {
+    order
+        .iter()
+        .enumerate()
+        .map(|(p, n)| (n.clone(), p))
+        .collect()
+}
+
+// The set of positions of the nodes reachable from every node, by position in `order`.
+fn descendant_sets<T, E>(
+    order: &[NodeRef<T, E>],
+    position: &HashMap<NodeRef<T, E>, usize>,
+) -> Vec<BitSet> {
+    let mut descendants = vec![BitSet::new(0); order.len()];
+    for (p, node) in order.iter().enumerate().rev() {
+        let mut reachable = BitSet::new(order.len());
+        for m in &node.ptr.borrow().outgoing {
+            let q = position[m];
+            if !reachable.contains(q) {
+                reachable.insert(q);
+                reachable.union_with(&descendants[q]);
+            }
+        }
+        descendants[p] = reachable;
+    }
+    descendants
+}
+
+// The transitive closure of a DAG as a bit matrix, answering reachability questions in
+// constant time at the cost of n^2 bits of memory. It describes the graph at the time
+// it was built, and only knows about the nodes that were in it then.
+pub struct ReachabilityMatrix<T, E = ()> {
+    position: HashMap<NodeRef<T, E>, usize>,
+    // rows[p] is the set of positions of the nodes reachable from the node at position p
+    rows: Vec<BitSet>,
+}
+impl<T, E> ReachabilityMatrix<T, E> {
+    // Whether there is a path from `from` to `to`. Every node reaches itself.
+    pub fn reaches(&self, from: &NodeRef<T, E>, to: &NodeRef<T, E>) -> bool {
+        from == to || self.rows[self.position[from]].contains(self.position[to])
+    }
+}
+
+// A reachability index based on a chain decomposition: the nodes are covered by the
+// minimum number k of disjoint paths, and for every node and every chain, the index
+// stores the first node of the chain that it reaches. Queries take constant time.
+// Like ReachabilityMatrix, it describes the graph at the time it was built.
+//
+// The index takes n * k 32-bit words, against the n^2 bits of a ReachabilityMatrix, so
+// it is only smaller when k < n / 32. That is the case for graphs that are mostly long
+// dependency chains, but k is at least the largest number of nodes none of which
+// reaches another, so a wide graph (e.g. many independent nodes) needs many chains.
+// Check chain_count() and use a ReachabilityMatrix when it is large.
+pub struct ChainIndex<T, E = ()> {
+    position: HashMap<NodeRef<T, E>, usize>,
+    // chain_of[p] is the (chain, index on that chain) of the node at position p
+    chain_of: Vec<(usize, u32)>,
+    first_reached: Vec<u32>,
+    chains: usize,
+}
+impl<T, E> ChainIndex<T, E> {
+    // The number of chains the nodes were divided into: the k in the n * k size.
+    pub fn chain_count(&self) -> usize {
+        self.chains
+    }
+
+    // Whether there is a path from `from` to `to`. Every node reaches itself.
+    pub fn reaches(&self, from: &NodeRef<T, E>, to: &NodeRef<T, E>) -> bool {
+        let (chain, index) = self.chain_of[self.position[to]];
+        self.first_reached[self.position[from] * self.chains + chain] <= index
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    fn edges(graph: &DirectedGraph<char>) -> Vec<String> {
+        graph
+            .nodes()
+            .iter()
+            .flat_map(|from| {
+                let from = from.ptr.borrow();
+                from.outgoing
+                    .iter()
+                    .map(|to| format!("{}{}", from.data, to.ptr.borrow().data))
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_transitive_reduction() {
+        // A -> B -> C -> D plus the shortcuts A -> C, A -> D, B -> D, a duplicate
+        // C -> D and an unrelated E -> D.
+        let mut graph = DirectedGraph::default();
+        let n: Vec<_> = ('A'..='E').map(|c| graph.add_node(c)).collect();
+        for &(from, to) in &[
+            (0, 3),
+            (0, 1),
+            (0, 2),
+            (1, 3),
+            (1, 2),
+            (2, 3),
+            (2, 3),
+            (4, 3),
+        ] {
+            graph.add_edge(&n[from], &n[to]);
+        }
+        graph.transitive_reduction().ok().unwrap();
+        assert_eq!(edges(&graph), vec!["AB", "BC", "CD", "ED"]);
+        assert_eq!(n[3].ptr.borrow().incoming, vec![n[2].clone(), n[4].clone()]);
+
+        // The diamond has no redundant edges.
+        let mut graph = DirectedGraph::default();
+        let n: Vec<_> = ('A'..='D').map(|c| graph.add_node(c)).collect();
+        for &(from, to) in &[(0, 1), (0, 2), (1, 3), (2, 3)] {
+            graph.add_edge(&n[from], &n[to]);
+        }
+        graph.transitive_reduction().ok().unwrap();
+        assert_eq!(edges(&graph), vec!["AB", "AC", "BD", "CD"]);
+
+        graph.add_edge(&n[3], &n[0]);
+        graph.add_edge(&n[0], &n[3]);
+        assert!(graph.transitive_reduction().is_err());
+        assert_eq!(edges(&graph).len(), 6);
+    }
+
+    #[test]
+    fn test_transitive_closure() {
+        // A -> B -> C, A -> C, D -> C, and E on its own.
+        let mut graph = DirectedGraph::default();
+        let n: Vec<_> = ('A'..='E').map(|c| graph.add_node(c)).collect();
+        for &(from, to) in &[(0, 1), (1, 2), (0, 2), (3, 2)] {
+            graph.add_edge(&n[from], &n[to]);
+        }
+        graph.add_edge(&n[1], &n[2]);
+        graph.transitive_closure().ok().unwrap();
+        assert_eq!(edges(&graph), vec!["AB", "AC", "BC", "BC", "DC"]);
+
+        graph.remove_edge(&n[0], &n[2]);
+        graph.add_edge(&n[2], &n[4]);
+        graph.transitive_closure().ok().unwrap();
+        assert_eq!(
+            edges(&graph),
+            vec!["AB", "AC", "AE", "BC", "BC", "BE", "CE", "DC", "DE"]
+        );
+    }
+
+    #[test]
+    fn test_reachability_indexes() {
+        // A DAG on 0..n with i -> j whenever j is a multiple of i, plus a few chains.
+        let n = 40;
+        let mut graph = DirectedGraph::default();
+        let nodes: Vec<_> = (0..n).map(|i| graph.add_node(i)).collect();
+        for i in 2..n {
+            for j in (2 * i..n).step_by(i) {
+                graph.add_edge(&nodes[i], &nodes[j]);
+            }
+        }
+        for i in 0..n - 3 {
+            if i % 5 != 4 {
+                graph.add_edge(&nodes[i], &nodes[i + 3]);
+            }
+        }
+
+        // Greedily extending chains in order could give A B, D and C; the minimum is two.
+        let mut small = DirectedGraph::default();
+        let [a, b, c, d] = ['A', 'B', 'C', 'D'].map(|x| small.add_node(x));
+        small.add_edge(&a, &b);
+        small.add_edge(&a, &c);
+        small.add_edge(&d, &b);
+        assert_eq!(small.chain_index().ok().unwrap().chain_count(), 2);
+
+        let matrix = graph.reachability_matrix().ok().unwrap();
+        let index = graph.chain_index().ok().unwrap();
+        assert!(index.chain_count() < n);
+        for from in &nodes {
+            for to in &nodes {
+                let expected = graph.is_reachable(from, to);
+                assert_eq!(matrix.reaches(from, to), expected);
+                assert_eq!(index.reaches(from, to), expected);
+            }
+        }
+
+        graph.add_edge(&nodes[n - 1], &nodes[1]);
+        assert!(graph.reachability_matrix().is_err());
+        assert!(graph.chain_index().is_err());
+    }
+}
@@ -0,0 +1,299 @@
+// Graph traversals starting from one or more nodes and following either the outgoing
+// or the incoming edges: depth-first (pre-order and post-order) and breadth-first
+// iterators, and a depth-first search reporting its events to a Visitor.
+//
+// Every node is visited at most once, even if several start nodes reach it.
+// Neighbours are visited in the order of the node's edge list.
+
+use crate::NodeRef;
+use std::collections::{HashSet, VecDeque};
+
+// Which edges a traversal follows.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Direction {
+    Outgoing,
+    Incoming,
+}
+
+fn neighbours<T, E>(node: &NodeRef<T, E>, direction: Direction) -> Vec<NodeRef<T, E>> {
+    let node = node.ptr.borrow();
+    match direction {
+        Direction::Outgoing => node.outgoing.clone(),
+        Direction::Incoming => node.incoming.clone(),
+    }
+}
+
+// Depth-first pre-order: every node is yielded before its descendants.
+pub struct Dfs<T, E = ()> {
+    stack: Vec<NodeRef<T, E>>,
+    discovered: HashSet<NodeRef<T, E>>,
+    direction: Direction,
+}
+impl<T, E> Dfs<T, E> {
+    pub fn new(starts: &[NodeRef<T, E>], direction: Direction) -> Self {
+        Dfs {
+            stack: starts.iter().rev().cloned().collect(),
+            discovered: HashSet::new(),
+            direction,
+        }
+    }
+}
+impl<T, E> Iterator for Dfs<T, E> {
+    type Item = NodeRef<T, E>;
+
+    fn next(&mut self) -> Option<NodeRef<T, E>> {
+        while let Some(node) = self.stack.pop() {
+            if self.discovered.insert(node.clone()) {
+                let discovered = &self.discovered;
+                let next = neighbours(&node, self.direction);
+                self.stack
+                    .extend(next.into_iter().rev().filter(|m| !discovered.contains(m)));
+                return Some(node);
+            }
+        }
+        None
+    }
+}
+
+// Depth-first post-order: every node is yielded after all of its descendants (unless
+// they are also its ancestors, i.e. on a cycle with it).
+pub struct DfsPostOrder<T, E = ()> {
+    starts: VecDeque<NodeRef<T, E>>,
+    // The current path, with the neighbours still to be explored of every node on it.
+    #[allow(clippy::type_complexity)]
+    stack: Vec<(NodeRef<T, E>, std::vec::IntoIter<NodeRef<T, E>>)>,
+    discovered: HashSet<NodeRef<T, E>>,
+    direction: Direction,
+}
+impl<T, E> DfsPostOrder<T, E> {
+    pub fn new(starts: &[NodeRef<T, E>], direction: Direction) -> Self {
+        DfsPostOrder {
+            starts: starts.iter().cloned().collect(),
+            stack: Vec::new(),
+            discovered: HashSet::new(),
+            direction,
+        }
+    }
+
+    fn push(&mut self, node: NodeRef<T, E>) {
+        let next = neighbours(&node, self.direction).into_iter();
+        self.discovered.insert(node.clone());
+        self.stack.push((node, next));
+    }
+}
+impl<T, E> Iterator for DfsPostOrder<T, E> {
+    type Item = NodeRef<T, E>;
+
+    fn next(&mut self) -> Option<NodeRef<T, E>> {
+        loop {
+            let discovered = &self.discovered;
+            match self.stack.last_mut() {
+                Some((_, next)) => match next.find(|m| !discovered.contains(m)) {
+                    Some(m) => self.push(m),
+                    None => return self.stack.pop().map(|(node, _)| node),
+                },
+                None => {
+                    let start = self.starts.pop_front()?;
+                    if !self.discovered.contains(&start) {
+                        self.push(start);
+                    }
+                }
+            }
+        }
+    }
+}
+
+// Breadth-first: nodes are yielded in order of their distance from the start nodes.
+pub struct Bfs<T, E = ()> {
+    queue: VecDeque<NodeRef<T, E>>,
+    discovered: HashSet<NodeRef<T, E>>,
+    direction: Direction,
+}
+impl<T, E> Bfs<T, E> {
+    pub fn new(starts: &[NodeRef<T, E>], direction: Direction) -> Self {
+        let mut discovered = HashSet::new();
+        let queue = starts
+            .iter()
+            .filter(|n| discovered.insert((*n).clone()))
+            .cloned()
+            .collect();
+        Bfs {
+            queue,
+            discovered,
+            direction,
+        }
+    }
+}
+impl<T, E> Iterator for Bfs<T, E> {
+    type Item = NodeRef<T, E>;
+
+    fn next(&mut self) -> Option<NodeRef<T, E>> {
+        let node = self.queue.pop_front()?;
+        for m in neighbours(&node, self.direction) {
+            if self.discovered.insert(m.clone()) {
+                self.queue.push_back(m);
+            }
+        }
+        Some(node)
+    }
+}
+
+// The events of a depth-first search, see depth_first_visit. Edges are reported in the
+// direction they were traversed, so when following incoming edges, `from` is the node
+// being explored and `to` is one of its predecessors. All methods do nothing by default.
+pub trait Visitor<T, E = ()> {
+    // `node` is reached for the first time.
+    fn discover(&mut self, _node: &NodeRef<T, E>) {}
+    // All nodes reachable from `node` have been explored.
+    fn finish(&mut self, _node: &NodeRef<T, E>) {}
+    // `to` is discovered through this edge.
+    fn tree_edge(&mut self, _from: &NodeRef<T, E>, _to: &NodeRef<T, E>) {}
+    // `to` is an ancestor of `from` in the search (or `from` itself), so the edge
+    // closes a cycle.
+    fn back_edge(&mut self, _from: &NodeRef<T, E>, _to: &NodeRef<T, E>) {}
+    // `to` has already been finished.
+    fn forward_or_cross_edge(&mut self, _from: &NodeRef<T, E>, _to: &NodeRef<T, E>) {}
+}
+
+// Run a depth-first search from each of `starts` in turn, skipping the ones that have
+// already been reached, and report its events to `visitor`.
+pub fn depth_first_visit<T, E, V>(starts: &[NodeRef<T, E>], direction: Direction, visitor: &mut V)
+where
+    V: Visitor<T, E>,
+{
+    // Nodes are on_stack (gray) between discover and finish, and only discovered
+    // (black) after.
+    let mut discovered = HashSet::new();
+    let mut on_stack = HashSet::new();
+    for start in starts {
+        if !discovered.insert(start.clone()) {
+            continue;
+        }
+        on_stack.insert(start.clone());
+        visitor.discover(start);
+        let mut stack = vec![(start.clone(), neighbours(start, direction).into_iter())];
+        while let Some((node, next)) = stack.last_mut() {
+            match next.next() {
+                Some(m) => {
+                    if discovered.insert(m.clone()) {
+                        visitor.tree_edge(node, &m);
+                        on_stack.insert(m.clone());
+                        visitor.discover(&m);
+                        let next = neighbours(&m, direction).into_iter();
+                        stack.push((m, next));
+                    } else if on_stack.contains(&m) {
+                        visitor.back_edge(node, &m);
+                    } else {
+                        visitor.forward_or_cross_edge(node, &m);
+                    }
+                }
+                None => {
+                    on_stack.remove(node);
+                    visitor.finish(node);
+                    stack.pop();
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::visit::*;
+    use crate::DirectedGraph;
+
+    fn to_string(nodes: impl Iterator<Item = NodeRef<char>>) -> String {
+        nodes.map(|node| node.ptr.borrow().data).collect()
+    }
+
+    // A -> B -> D -> E, A -> C -> D, E -> B, and a separate F -> C.
+    fn graph() -> (DirectedGraph<char>, Vec<NodeRef<char>>) {
+        let mut graph = DirectedGraph::default();
+        let nodes: Vec<_> = ('A'..='F').map(|c| graph.add_node(c)).collect();
+        let edge = |graph: &mut DirectedGraph<char>, from: usize, to: usize| {
+            graph.add_edge(&nodes[from], &nodes[to])
+        };
+        edge(&mut graph, 0, 1);
+        edge(&mut graph, 0, 2);
+        edge(&mut graph, 1, 3);
+        edge(&mut graph, 2, 3);
+        edge(&mut graph, 3, 4);
+        edge(&mut graph, 4, 1);
+        edge(&mut graph, 5, 2);
+        (graph, nodes)
+    }
+
+    #[test]
+    fn test_iterators() {
+        let (graph, nodes) = graph();
+        let a = &nodes[0..1];
+        assert_eq!(to_string(Dfs::new(a, Direction::Outgoing)), "ABDEC");
+        assert_eq!(
+            to_string(DfsPostOrder::new(a, Direction::Outgoing)),
+            "EDBCA"
+        );
+        assert_eq!(to_string(Bfs::new(a, Direction::Outgoing)), "ABCDE");
+
+        let d = &nodes[3..4];
+        assert_eq!(to_string(Dfs::new(d, Direction::Incoming)), "DBAECF");
+        assert_eq!(to_string(Bfs::new(d, Direction::Incoming)), "DBCAEF");
+
+        // Several start nodes; nodes reached from an earlier one are not repeated.
+        let starts = [nodes[5].clone(), nodes[0].clone()];
+        assert_eq!(to_string(Dfs::new(&starts, Direction::Outgoing)), "FCDEBA");
+        assert_eq!(
+            to_string(DfsPostOrder::new(&starts, Direction::Outgoing)),
+            "BEDCFA"
+        );
+        assert_eq!(
+            to_string(Bfs::new(graph.nodes(), Direction::Outgoing)),
+            "ABCDEF"
+        );
+    }
+
+    #[test]
+    fn test_visitor() {
+        #[derive(Default)]
+        struct Log(Vec<String>);
+        impl Visitor<char> for Log {
+            fn discover(&mut self, node: &NodeRef<char>) {
+                self.0.push(format!("discover {}", node.ptr.borrow().data));
+            }
+            fn finish(&mut self, node: &NodeRef<char>) {
+                self.0.push(format!("finish {}", node.ptr.borrow().data));
+            }
+            fn back_edge(&mut self, from: &NodeRef<char>, to: &NodeRef<char>) {
+                let (from, to) = (from.ptr.borrow().data, to.ptr.borrow().data);
+                self.0.push(format!("back {}{}", from, to));
+            }
+            fn forward_or_cross_edge(&mut self, from: &NodeRef<char>, to: &NodeRef<char>) {
+                let (from, to) = (from.ptr.borrow().data, to.ptr.borrow().data);
+                self.0.push(format!("cross {}{}", from, to));
+            }
+        }
+
+        let (_graph, nodes) = graph();
+        let mut log = Log::default();
+        depth_first_visit(&nodes, Direction::Outgoing, &mut log);
+        assert_eq!(
+            log.0,
+            vec![
+                "discover A",
+                "discover B",
+                "discover D",
+                "discover E",
+                "back EB",
+                "finish E",
+                "finish D",
+                "finish B",
+                "discover C",
+                "cross CD",
+                "finish C",
+                "finish A",
+                "discover F",
+                "cross FC",
+                "finish F",
+            ]
+        );
+    }
+}
@@ -0,0 +1,465 @@
+// Just enough XML for the GraphML and GEXF formats: a reader that builds a tree of
+// elements, and helpers for the writers.
+//
+// The reader handles comments, processing instructions, a DOCTYPE (which is skipped),
+// CDATA sections and the predefined and numeric character references. Namespace
+// prefixes are dropped from element names, so <y:ShapeNode> is read as ShapeNode;
+// attribute names are kept as written.
+
+use crate::Attributes;
+use std::collections::HashSet;
+use std::error::Error;
+use std::fmt;
+
+// The error returned for malformed XML, or for a well-formed document that does not
+// describe a graph that can be read. Lines and columns start at 1, and columns count
+// characters.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct XmlError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for XmlError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "line {}, column {}: {}",
+            self.line, self.column, self.message
+        )
+    }
+}
+
+impl Error for XmlError {}
+
+pub(crate) struct Element {
+    // The name without its namespace prefix.
+    pub(crate) name: String,
+    pub(crate) attributes: Vec<(String, String)>,
+    pub(crate) children: Vec<Content>,
+    // Where the start tag begins.
+    line: usize,
+    column: usize,
+}
+
+pub(crate) enum Content {
+    Element(Element),
+    Text(String),
+}
+
+impl Element {
+    pub(crate) fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub(crate) fn required_attribute(&self, name: &str) -> Result<&str, XmlError> {
+        self.attribute(name).ok_or_else(|| {
+            self.error(format!(
+                "<{}> is missing the '{}' attribute",
+                self.name, name
+            ))
+        })
+    }
+
+    // The child elements with the given name.
+    pub(crate) fn elements<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Element> {
+        self.children.iter().filter_map(move |child| match child {
+            Content::Element(element) if element.name == name => Some(element),
+            _ => None,
+        })
+    }
+
+    // The text directly inside the element.
+    pub(crate) fn text(&self) -> String {
+        self.children
+            .iter()
+            .filter_map(|child| match child {
+                Content::Text(text) => Some(text.as_str()),
+                Content::Element(_) => None,
+            })
+            .collect()
+    }
+
+    // An error at the start tag of this element.
+    pub(crate) fn error(&self, message: String) -> XmlError {
+        XmlError {
+            line: self.line,
+            column: self.column,
+            message,
+        }
+    }
+}
+
+// Escape a string for use in text or in a quoted attribute value.
+pub(crate) fn escape(s: &str) -> String {
+    let mut result = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => result.push_str("&amp;"),
+            '<' => result.push_str("&lt;"),
+            '>' => result.push_str("&gt;"),
+            '"' => result.push_str("&quot;"),
+            '\'' => result.push_str("&apos;"),
+            c => result.push(c),
+        }
+    }
+    result
+}
+
+// The distinct attribute names in `lists`, in order of first appearance, for the
+// attribute declarations of the writers.
+pub(crate) fn attribute_names(lists: &[Attributes]) -> Vec<&str> {
+    let mut seen = HashSet::new();
+    lists
+        .iter()
+        .flatten()
+        .map(|(name, _)| name.as_str())
+        .filter(|name| seen.insert(*name))
+        .collect()
+}
+
+// Parse a document and return its root element.
+pub(crate) fn parse(input: &str) -> Result<Element, XmlError> {
+    let mut reader = Reader {
+        chars: input.chars().collect(),
+        pos: 0,
+        line: 1,
+        column: 1,
+    };
+    reader.skip_misc()?;
+    if reader.peek(0) != Some('<') {
+        return Err(reader.error("expected the root element".into()));
+    }
+    let root = reader.element()?;
+    reader.skip_misc()?;
+    if reader.peek(0).is_some() {
+        return Err(reader.error("unexpected content after the root element".into()));
+    }
+    Ok(root)
+}
+
+struct Reader {
+    chars: Vec<char>,
+    pos: usize,
+    line: usize,
+    column: usize,
+}
+
+impl Reader {
+    fn peek(&self, ahead: usize) -> Option<char> {
+        self.chars.get(self.pos + ahead).copied()
+    }
+
+    fn starts_with(&self, s: &str) -> bool {
+        s.chars().enumerate().all(|(i, c)| self.peek(i) == Some(c))
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek(0)?;
+        self.pos += 1;
+        if c == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+        Some(c)
+    }
+
+    fn bump_str(&mut self, s: &str) {
+        for _ in s.chars() {
+            self.bump();
+        }
+    }
+
+    // An error at the current position.
+    fn error(&self, message: String) -> XmlError {
+        XmlError {
+            line: self.line,
+            column: self.column,
+            message,
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while matches!(self.peek(0), Some(c) if c.is_whitespace()) {
+            self.bump();
+        }
+    }
+
+    // Skip from `start` up to and including `end`.
+    fn skip_past(&mut self, start: &str, end: &str, what: &str) -> Result<(), XmlError> {
+        let error = self.error(format!("unterminated {}", what));
+        self.bump_str(start);
+        while !self.starts_with(end) {
+            if self.bump().is_none() {
+                return Err(error);
+            }
+        }
+        self.bump_str(end);
+        Ok(())
+    }
+
+    // Skip whitespace, comments, processing instructions and a DOCTYPE outside of the
+    // root element.
+    fn skip_misc(&mut self) -> Result<(), XmlError> {
+        loop {
+            self.skip_whitespace();
+            if self.starts_with("<!--") {
+                self.skip_past("<!--", "-->", "comment")?;
+            } else if self.starts_with("<?") {
+                self.skip_past("<?", "?>", "processing instruction")?;
+            } else if self.starts_with("<!DOCTYPE") {
+                let error = self.error("unterminated DOCTYPE".into());
+                // The internal subset in brackets may contain '>'.
+                let mut depth = 0;
+                loop {
+                    match self.bump() {
+                        None => return Err(error),
+                        Some('[') => depth += 1,
+                        Some(']') => depth -= 1,
+                        Some('>') if depth == 0 => break,
+                        Some(_) => {}
+                    }
+                }
+            } else {
+                return Ok(());
+            }
+        }
+    }
+
+    fn name(&mut self) -> Result<String, XmlError> {
+        let mut name = String::new();
+        while let Some(c) = self.peek(0) {
+            if c.is_whitespace() || "/>=<\"'".contains(c) {
+                break;
+            }
+            name.push(c);
+            self.bump();
+        }
+        if name.is_empty() {
+            return Err(match self.peek(0) {
+                Some(c) => self.error(format!("expected a name, found '{}'", c)),
+                None => self.error("expected a name, found end of input".into()),
+            });
+        }
+        Ok(name)
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), XmlError> {
+        match self.peek(0) {
+            Some(found) if found == c => {
+                self.bump();
+                Ok(())
+            }
+            Some(found) => Err(self.error(format!("expected '{}', found '{}'", c, found))),
+            None => Err(self.error(format!("expected '{}', found end of input", c))),
+        }
+    }
+
+    // An element, starting at its '<'.
+    fn element(&mut self) -> Result<Element, XmlError> {
+        let (line, column) = (self.line, self.column);
+        self.bump();
+        let tag = self.name()?;
+        let mut element = Element {
+            name: local_name(&tag).to_string(),
+            attributes: Vec::new(),
+            children: Vec::new(),
+            line,
+            column,
+        };
+        loop {
+            self.skip_whitespace();
+            if self.starts_with("/>") {
+                self.bump_str("/>");
+                return Ok(element);
+            }
+            if self.peek(0) == Some('>') {
+                self.bump();
+                break;
+            }
+            let (line, column) = (self.line, self.column);
+            let name = self.name()?;
+            self.skip_whitespace();
+            self.expect('=')?;
+            self.skip_whitespace();
+            let value = self.attribute_value()?;
+            if element.attribute(&name).is_some() {
+                return Err(XmlError {
+                    line,
+                    column,
+                    message: format!("duplicate attribute '{}'", name),
+                });
+            }
+            element.attributes.push((name, value));
+        }
+        loop {
+            if self.starts_with("</") {
+                let (line, column) = (self.line, self.column);
+                self.bump_str("</");
+                let end = self.name()?;
+                if end != tag {
+                    return Err(XmlError {
+                        line,
+                        column,
+                        message: format!("expected </{}>, found </{}>", tag, end),
+                    });
+                }
+                self.skip_whitespace();
+                self.expect('>')?;
+                return Ok(element);
+            } else if self.starts_with("<!--") {
+                self.skip_past("<!--", "-->", "comment")?;
+            } else if self.starts_with("<?") {
+                self.skip_past("<?", "?>", "processing instruction")?;
+            } else if self.starts_with("<![CDATA[") {
+                let error = self.error("unterminated CDATA section".into());
+                self.bump_str("<![CDATA[");
+                let mut text = String::new();
+                while !self.starts_with("]]>") {
+                    text.push(self.bump().ok_or_else(|| error.clone())?);
+                }
+                self.bump_str("]]>");
+                element.children.push(Content::Text(text));
+            } else if self.peek(0) == Some('<') {
+                let child = self.element()?;
+                element.children.push(Content::Element(child));
+            } else if self.peek(0).is_none() {
+                return Err(element.error(format!("unterminated element <{}>", tag)));
+            } else {
+                let mut text = String::new();
+                while let Some(c) = self.peek(0) {
+                    match c {
+                        '<' => break,
+                        '&' => text.push(self.reference()?),
+                        c => {
+                            self.bump();
+                            text.push(c);
+                        }
+                    }
+                }
+                element.children.push(Content::Text(text));
+            }
+        }
+    }
+
+    fn attribute_value(&mut self) -> Result<String, XmlError> {
+        let quote = match self.peek(0) {
+            Some(c) if c == '"' || c == '\'' => c,
+            _ => return Err(self.error("expected a quoted attribute value".into())),
+        };
+        let error = self.error("unterminated attribute value".into());
+        self.bump();
+        let mut value = String::new();
+        loop {
+            match self.peek(0) {
+                None => return Err(error),
+                Some(c) if c == quote => {
+                    self.bump();
+                    return Ok(value);
+                }
+                Some('<') => return Err(self.error("'<' in attribute value".into())),
+                Some('&') => value.push(self.reference()?),
+                Some(c) => {
+                    self.bump();
+                    value.push(c);
+                }
+            }
+        }
+    }
+
+    // A character or entity reference, starting at its '&'.
+    fn reference(&mut self) -> Result<char, XmlError> {
+        let error = |reader: &Reader, reference: &str| {
+            reader.error(format!("unknown reference '&{};'", reference))
+        };
+        let start = self.pos + 1;
+        let end = match self.chars[start..].iter().take(16).position(|&c| c == ';') {
+            Some(length) => start + length,
+            None => return Err(self.error("unterminated reference".into())),
+        };
+        let reference: String = self.chars[start..end].iter().collect();
+        let c = match reference.as_str() {
+            "lt" => '<',
+            "gt" => '>',
+            "amp" => '&',
+            "quot" => '"',
+            "apos" => '\'',
+            _ => {
+                let code = if let Some(hex) = reference.strip_prefix("#x") {
+                    u32::from_str_radix(hex, 16).ok()
+                } else if let Some(decimal) = reference.strip_prefix('#') {
+                    decimal.parse().ok()
+                } else {
+                    None
+                };
+                match code.and_then(char::from_u32) {
+                    Some(c) => c,
+                    None => return Err(error(self, &reference)),
+                }
+            }
+        };
+        for _ in self.pos..=end {
+            self.bump();
+        }
+        Ok(c)
+    }
+}
+
+fn local_name(name: &str) -> &str {
+    name.rsplit(':').next().unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::xml::*;
+
+    #[test]
+    fn test_parse() {
+        let input = "<?xml version=\"1.0\"?>\n\
+                     <!DOCTYPE root [<!ENTITY x \"y\">]>\n\
+                     <!-- comment -->\n\
+                     <root a='1 &amp; 2' b=\"&#x3C;&#62;\">\n\
+                     \x20 <y:child>x &lt; y<![CDATA[ & <z>]]></y:child>\n\
+                     \x20 <child/><?pi?>\n\
+                     </root>\n";
+        let root = parse(input).unwrap();
+        assert_eq!(root.name, "root");
+        assert_eq!(root.attribute("a"), Some("1 & 2"));
+        assert_eq!(root.attribute("b"), Some("<>"));
+        let children: Vec<_> = root.elements("child").collect();
+        assert_eq!(children.len(), 2);
+        assert_eq!(children[0].text(), "x < y & <z>");
+        assert_eq!(
+            escape("<a href=\"x\">&'"),
+            "&lt;a href=&quot;x&quot;&gt;&amp;&apos;"
+        );
+
+        let error = |input: &str| parse(input).err().unwrap().to_string();
+        assert_eq!(
+            error("<a>\n  <b></a>"),
+            "line 2, column 6: expected </b>, found </a>"
+        );
+        assert_eq!(
+            error("<a x='1' x='2'/>"),
+            "line 1, column 10: duplicate attribute 'x'"
+        );
+        assert_eq!(
+            error("<a>&nbsp;</a>"),
+            "line 1, column 4: unknown reference '&nbsp;'"
+        );
+        assert_eq!(
+            error("<a>\n<b>"),
+            "line 2, column 1: unterminated element <b>"
+        );
+        assert_eq!(
+            error("<a/><b/>"),
+            "line 1, column 5: unexpected content after the root element"
+        );
+    }
+}